@@ -0,0 +1,344 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_yaml::{Mapping, Value};
+
+// connection settings, the ansible_* names are accepted so existing inventories can be reused
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HostVars {
+    #[serde(alias = "ansible_host")]
+    pub host: Option<String>,
+    #[serde(alias = "ansible_user")]
+    pub user: Option<String>,
+    #[serde(alias = "ansible_port")]
+    pub port: Option<u16>,
+    #[serde(alias = "ansible_ssh_private_key_file")]
+    pub identity_file: Option<String>,
+}
+
+impl HostVars {
+    // values set in `other` win
+    fn merge(&self, other: &HostVars) -> HostVars {
+        HostVars {
+            host: other.host.clone().or_else(|| self.host.clone()),
+            user: other.user.clone().or_else(|| self.user.clone()),
+            port: other.port.or(self.port),
+            identity_file: other
+                .identity_file
+                .clone()
+                .or_else(|| self.identity_file.clone()),
+        }
+    }
+}
+
+// `hosts: [web-01, web-02]` or ansible's `hosts: {web-01: {port: 2222}, web-02: }`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum GroupHosts {
+    List(Vec<String>),
+    Map(BTreeMap<String, Option<HostVars>>),
+}
+
+impl Default for GroupHosts {
+    fn default() -> Self {
+        GroupHosts::List(Vec::new())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Group {
+    #[serde(default)]
+    hosts: GroupHosts,
+    #[serde(default)]
+    vars: HostVars,
+    #[serde(default, deserialize_with = "empty_groups")]
+    children: BTreeMap<String, Group>,
+}
+
+// ansible leaves groups without hosts or vars empty, `children: {web: }`
+fn empty_groups<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, Group>, D::Error> {
+    let groups = BTreeMap::<String, Option<Group>>::deserialize(deserializer)?;
+    Ok(groups
+        .into_iter()
+        .map(|(k, v)| (k, v.unwrap_or_default()))
+        .collect())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Host {
+    pub name: String,
+    pub vars: HostVars,
+    // every group the host is in, ancestors included
+    pub groups: BTreeSet<String>,
+}
+
+impl Host {
+    pub fn address(&self) -> &str {
+        self.vars.host.as_deref().unwrap_or(&self.name)
+    }
+}
+
+// collected while walking the groups, vars are resolved once every group has been seen
+#[derive(Debug, Default)]
+struct HostEntry {
+    groups: BTreeSet<String>,
+    // own vars of every group the host is in, with the group's depth below `all`
+    group_vars: Vec<(usize, HostVars)>,
+    host_vars: HostVars,
+}
+
+impl HostEntry {
+    // like ansible: deeper groups beat their parents, vars set on the host itself beat any group
+    fn into_host(mut self, name: String) -> Host {
+        self.group_vars.sort_by_key(|x| x.0);
+        let vars = self
+            .group_vars
+            .iter()
+            .fold(HostVars::default(), |acc, (_, x)| acc.merge(x))
+            .merge(&self.host_vars);
+        Host {
+            name,
+            vars,
+            groups: self.groups,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inventory {
+    // in order of first appearance, child groups are walked by name
+    pub hosts: Vec<Host>,
+}
+
+fn from_value<T: de::DeserializeOwned>(x: Value) -> Result<T, serde_yaml::Error> {
+    serde_yaml::from_value(x)
+}
+
+impl Inventory {
+    // three layouts are accepted: ansible's `all:` root, top-level groups (`web: {hosts: ...}`)
+    // which become children of `all`, and the old flat `hosts: [...]` file
+    pub fn from_yaml(s: &str) -> Result<Inventory, serde_yaml::Error> {
+        let top: BTreeMap<String, Value> = serde_yaml::from_str(s)?;
+        let mut flat = Mapping::new();
+        let mut groups = BTreeMap::new();
+        for (k, v) in top {
+            match k.as_str() {
+                "hosts" | "vars" | "children" => {
+                    flat.insert(Value::String(k), v);
+                }
+                _ => {
+                    groups.insert(k, from_value::<Option<Group>>(v)?.unwrap_or_default());
+                }
+            }
+        }
+        let mut all = match groups.remove("all") {
+            Some(_) if !flat.is_empty() => {
+                return Err(de::Error::custom(
+                    "`all` can't be combined with top-level hosts, vars or children",
+                ));
+            }
+            Some(all) => all,
+            None => from_value(Value::Mapping(flat))?,
+        };
+        for (name, group) in groups {
+            if all.children.contains_key(&name) {
+                return Err(de::Error::custom(format!(
+                    "group `{}` is defined twice",
+                    name
+                )));
+            }
+            all.children.insert(name, group);
+        }
+
+        let mut order = Vec::new();
+        let mut entries = BTreeMap::new();
+        walk("all", &all, &[], &mut order, &mut entries);
+        let hosts = order
+            .into_iter()
+            .map(|name| {
+                let entry = entries.remove(&name).unwrap_or_default();
+                entry.into_host(name)
+            })
+            .collect();
+        Ok(Inventory { hosts })
+    }
+
+    // ansible-style patterns separated by ',' or ':', e.g. "web:!web-03", "web*:&prod".
+    // a term is a group name or a host glob, '!' excludes and '&' intersects
+    pub fn limit(&self, pattern: &str) -> Vec<Host> {
+        let mut includes = Vec::new();
+        let mut intersects = Vec::new();
+        let mut excludes = Vec::new();
+        for term in pattern
+            .split([',', ':'])
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+        {
+            if let Some(x) = term.strip_prefix('!') {
+                excludes.push(x);
+            } else if let Some(x) = term.strip_prefix('&') {
+                intersects.push(x);
+            } else {
+                includes.push(term);
+            }
+        }
+        self.hosts
+            .iter()
+            .filter(|host| {
+                (includes.is_empty() || includes.iter().any(|x| matches(host, x)))
+                    && intersects.iter().all(|x| matches(host, x))
+                    && !excludes.iter().any(|x| matches(host, x))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+fn walk<'a>(
+    name: &'a str,
+    group: &'a Group,
+    parents: &[(&'a str, &'a HostVars)],
+    order: &mut Vec<String>,
+    entries: &mut BTreeMap<String, HostEntry>,
+) {
+    let mut path = parents.to_vec();
+    path.push((name, &group.vars));
+    let hosts: Vec<(&String, Option<&HostVars>)> = match &group.hosts {
+        GroupHosts::List(xs) => xs.iter().map(|x| (x, None)).collect(),
+        GroupHosts::Map(xs) => xs.iter().map(|(k, v)| (k, v.as_ref())).collect(),
+    };
+    for (host_name, host_vars) in hosts {
+        let entry = entries.entry(host_name.clone()).or_insert_with(|| {
+            order.push(host_name.clone());
+            HostEntry::default()
+        });
+        entry.groups.extend(path.iter().map(|x| x.0.to_string()));
+        entry.group_vars.extend(
+            path.iter()
+                .enumerate()
+                .map(|(depth, x)| (depth, x.1.clone())),
+        );
+        if let Some(x) = host_vars {
+            entry.host_vars = entry.host_vars.merge(x);
+        }
+    }
+    for (child_name, child) in &group.children {
+        walk(child_name, child, &path, order, entries);
+    }
+}
+
+fn matches(host: &Host, term: &str) -> bool {
+    glob_match(term, &host.name) || host.groups.iter().any(|x| glob_match(term, x))
+}
+
+// '*' matches any run of characters and '?' a single one
+fn glob_match(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    let (mut p, mut i) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while i < s.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == s[i]) {
+            p += 1;
+            i += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, i));
+            p += 1;
+        } else if let Some((sp, si)) = star {
+            p = sp + 1;
+            i = si + 1;
+            star = Some((sp, si + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|x| *x == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVENTORY: &str = "
+all:
+  vars:
+    ansible_user: deploy
+  children:
+    web:
+      vars:
+        port: 2222
+      hosts:
+        web-01:
+          user: admin
+        web-02:
+        web-03:
+          ansible_host: 10.0.0.3
+    prod:
+      children:
+        web-prod:
+          hosts: [web-01, web-02]
+    db:
+      hosts:
+        db-01:
+        web-01:
+";
+
+    fn names(hosts: &[Host]) -> Vec<&str> {
+        hosts.iter().map(|x| x.name.as_str()).collect()
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("web-*", "web-01"));
+        assert!(glob_match("w?b-0*", "web-03"));
+        assert!(glob_match("*", "db-01"));
+        assert!(!glob_match("web-*", "db-01"));
+        assert!(!glob_match("web-0?", "web-1"));
+    }
+
+    #[test]
+    fn limit_patterns() {
+        let inventory = Inventory::from_yaml(INVENTORY).unwrap();
+        assert_eq!(
+            names(&inventory.hosts),
+            ["db-01", "web-01", "web-02", "web-03"]
+        );
+        assert_eq!(names(&inventory.limit("web:!web-03")), ["web-01", "web-02"]);
+        assert_eq!(names(&inventory.limit("web*:&prod")), ["web-01", "web-02"]);
+        assert_eq!(
+            names(&inventory.limit("db,web-03")),
+            ["db-01", "web-01", "web-03"]
+        );
+        assert_eq!(names(&inventory.limit("all:!web")), ["db-01"]);
+    }
+
+    #[test]
+    fn var_inheritance() {
+        let inventory = Inventory::from_yaml(INVENTORY).unwrap();
+        let host = |name: &str| inventory.hosts.iter().find(|x| x.name == name).unwrap();
+        // host vars beat `all.vars` even though web-01 shows up again under db
+        assert_eq!(host("web-01").vars.user.as_deref(), Some("admin"));
+        assert_eq!(host("web-01").vars.port, Some(2222));
+        assert_eq!(host("web-02").vars.user.as_deref(), Some("deploy"));
+        assert_eq!(host("web-03").address(), "10.0.0.3");
+        assert_eq!(host("db-01").vars.port, None);
+        assert!(host("web-01").groups.contains("web-prod"));
+        assert!(host("web-01").groups.contains("prod"));
+    }
+
+    #[test]
+    fn flat_and_top_level_groups() {
+        let inventory = Inventory::from_yaml("hosts:\n  - a\n  - b\n").unwrap();
+        assert_eq!(names(&inventory.hosts), ["a", "b"]);
+        assert!(inventory.hosts[0].groups.contains("all"));
+
+        let inventory =
+            Inventory::from_yaml("web:\n  hosts:\n    web-01:\ndb:\n  hosts: [db-01]\n").unwrap();
+        assert_eq!(names(&inventory.hosts), ["db-01", "web-01"]);
+        assert_eq!(names(&inventory.limit("web")), ["web-01"]);
+
+        assert!(Inventory::from_yaml("all:\n  hosts: [a]\nhosts: [b]\n").is_err());
+    }
+}
@@ -1,18 +1,31 @@
+mod inventory;
+
 use std::{error::Error, string};
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::env;
+use std::time::{Duration, Instant};
+
+use futures::{Future, FutureExt, stream::{self, BoxStream, StreamExt}};
+use openssh::{KnownHosts, Session, SessionBuilder};
+use tokio::time::{sleep, timeout};
 
-use futures::{FutureExt, stream::{self, StreamExt}};
-use openssh::{KnownHosts, Session};
-use serde::{Deserialize, Serialize};
+use inventory::{Host, Inventory};
 
 #[derive(Debug)]
 enum MyError {
     OpenSshError(openssh::Error),
     JoinError(tokio::task::JoinError),
     FromUtf8Error(string::FromUtf8Error),
+    ConnectTimeout(Duration),
+    CommandTimeout(Duration),
+}
+
+impl MyError {
+    fn is_timeout(&self) -> bool {
+        matches!(self, MyError::ConnectTimeout(_) | MyError::CommandTimeout(_))
+    }
 }
 
 impl From<openssh::Error> for MyError {
@@ -33,58 +46,431 @@ impl From<string::FromUtf8Error> for MyError {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
-struct Hosts {
-    hosts: Vec<String>,
+// what a single host returned, exit_code is None when the remote command was killed by a signal
+#[derive(Debug)]
+struct CommandResult {
+    stdout: String,
+    stderr: String,
+    exit_code: Option<i32>,
+    duration: Duration,
+}
+
+impl CommandResult {
+    fn from_output(output: std::process::Output, duration: Duration) -> Result<CommandResult, MyError> {
+        Ok(CommandResult {
+            stdout: String::from_utf8(output.stdout)?,
+            stderr: String::from_utf8(output.stderr)?,
+            exit_code: output.status.code(),
+            duration,
+        })
+    }
+
+    fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+// hosts by outcome, in the order their results were printed
+#[derive(Debug, Default)]
+struct Summary {
+    succeeded: Vec<String>,
+    failed: Vec<String>,
+    timed_out: Vec<String>,
+}
+
+impl Summary {
+    fn record(&mut self, host: String, result: &Result<CommandResult, MyError>) {
+        match result {
+          Ok(r) if r.success() => self.succeeded.push(host),
+          Err(e) if e.is_timeout() => self.timed_out.push(host),
+          _ => self.failed.push(host),
+        }
+    }
+
+    // the process exits non-zero when any host failed or timed out
+    fn exit_code(&self) -> i32 {
+        if self.failed.is_empty() && self.timed_out.is_empty() { 0 } else { 1 }
+    }
+}
+
+const DEFAULT_PARALLEL: usize = 10;
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// a hung command must not block the whole run, `--timeout 0` lifts the limit
+const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(600);
+const DEFAULT_RETRIES: u32 = 2;
+const MAX_RETRIES: u32 = 10;
+// doubled after every failed connect, up to MAX_RETRY_BACKOFF
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+struct SshOptions {
+    connect_timeout: Duration,
+    // no limit when None
+    command_timeout: Option<Duration>,
+    // extra connect attempts, the command itself is never retried
+    retries: u32,
+    known_hosts: KnownHosts,
+}
+
+impl Default for SshOptions {
+    fn default() -> Self {
+        SshOptions {
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            command_timeout: Some(DEFAULT_COMMAND_TIMEOUT),
+            retries: DEFAULT_RETRIES,
+            known_hosts: KnownHosts::Accept,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    path: String,
+    cmd: String,
+    // at most this many ssh sessions at once
+    parallel: usize,
+    // print in inventory order instead of as hosts finish
+    ordered: bool,
+    // ansible-style host pattern, e.g. "web:!web-03"
+    limit: Option<String>,
+    ssh: SshOptions,
+}
+
+fn parse_secs(flag: &str, value: &str) -> Result<Duration, String> {
+    match value.parse::<f64>() {
+        Ok(x) if x > 0.0 && x.is_finite() => Ok(Duration::from_secs_f64(x)),
+        _ => Err(format!("invalid {}: {}", flag, value)),
+    }
+}
+
+fn parse_known_hosts(value: &str) -> Result<KnownHosts, String> {
+    match value {
+        "strict" => Ok(KnownHosts::Strict),
+        "add" => Ok(KnownHosts::Add),
+        "accept" => Ok(KnownHosts::Accept),
+        _ => Err(format!("invalid --known-hosts: {} (expected strict, add or accept)", value)),
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut positional = Vec::new();
+    let mut parallel = DEFAULT_PARALLEL;
+    let mut ordered = false;
+    let mut limit = None;
+    let mut ssh = SshOptions::default();
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--parallel" | "-p" => {
+                let n = iter.next().ok_or("--parallel needs a value")?;
+                parallel = n.parse().map_err(|_| format!("invalid --parallel: {}", n))?;
+                if parallel == 0 {
+                    return Err("--parallel must be at least 1".to_string());
+                }
+            }
+            "--ordered" => ordered = true,
+            "--limit" | "-l" => {
+                limit = Some(iter.next().ok_or("--limit needs a pattern")?.clone());
+            }
+            "--connect-timeout" => {
+                let x = iter.next().ok_or("--connect-timeout needs seconds")?;
+                ssh.connect_timeout = parse_secs(arg, x)?;
+            }
+            "--timeout" | "-t" => {
+                let x = iter.next().ok_or("--timeout needs seconds")?;
+                ssh.command_timeout = if x == "0" { None } else { Some(parse_secs(arg, x)?) };
+            }
+            "--retries" => {
+                let x = iter.next().ok_or("--retries needs a value")?;
+                ssh.retries = x
+                    .parse()
+                    .ok()
+                    .filter(|x| *x <= MAX_RETRIES)
+                    .ok_or_else(|| format!("invalid --retries: {} (0 to {})", x, MAX_RETRIES))?;
+            }
+            "--known-hosts" => {
+                let x = iter.next().ok_or("--known-hosts needs a policy")?;
+                ssh.known_hosts = parse_known_hosts(x)?;
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+    match &positional[..] {
+        [path, cmd] => Ok(Options { path: path.clone(), cmd: cmd.clone(), parallel, ordered, limit, ssh }),
+        _ => Err("expected a host file and a command".to_string()),
+    }
+}
+
+fn print_result(host: &str, result: &Result<CommandResult, MyError>) {
+    println!("============================== {} ==============================", host);
+    match result {
+      Ok(r) => {
+        print!("{}", r.stdout);
+        eprint!("{}", r.stderr);
+        println!("--- exit {} in {:.2?}", show_exit_code(r.exit_code), r.duration);
+      }
+      Err(MyError::ConnectTimeout(d)) => println!("--- timeout: no connection after {:.1?}", d),
+      Err(MyError::CommandTimeout(d)) => println!("--- timeout: command still running after {:.1?}", d),
+      Err(e) => println!("--- error: {:?}", e),
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
-    //let path = "/Users/ben.jeong/Develop/Works/story/story-deploy/projects/story-app-http/hosts/production.yml";
-    //let path = "/tmp/common.yml";
-    if args.len() < 2 {
-      eprintln!("Usage: {} {{host-yaml-file-path}} cmd", args[0]);
-      std::process::exit(-1);
-    }
-    let path = args[1].as_str();
-    let cmd = args[2].as_str();
-    let hosts = get_hosts(path)?;
-
-    let fibers = hosts
-        .iter()
-        .map(move |host| run_cmd(host.clone(), cmd.to_string()).map(move |x| x.map(|y| (host.clone(), y))))
-        .collect::<Vec<_>>();
-
-    let results: Vec<_> = stream::iter(fibers)
-        .then(|f| async move { f.await.unwrap() })
-        .collect()
-        .await;
-
-    results.iter().for_each(|line| {
-      println!("============================== {} ==============================", line.0);
-      println!("{}", line.1)
+    let options = match parse_args(&args) {
+      Ok(x) => x,
+      Err(e) => {
+        eprintln!("{}", e);
+        eprintln!("Usage: {} [--parallel N] [--ordered] [--limit PATTERN] [--connect-timeout SECS] [--timeout SECS, default 600, 0 for none] [--retries N] [--known-hosts strict|add|accept] {{inventory-yaml-file-path}} cmd", args[0]);
+        std::process::exit(-1);
+      }
+    };
+    let inventory = get_inventory(&options.path)?;
+    let hosts = match &options.limit {
+      Some(pattern) => inventory.limit(pattern),
+      None => inventory.hosts,
+    };
+    if hosts.is_empty() {
+      eprintln!("no hosts matched");
+      std::process::exit(1);
+    }
+
+    let cmd = options.cmd.clone();
+    let ssh = options.ssh;
+    let mut results = run_all(hosts, options.parallel, options.ordered, move |host| {
+        run_cmd(host, cmd.clone(), ssh.clone())
     });
 
+    let mut summary = Summary::default();
+    while let Some((host, result)) = results.next().await {
+        print_result(&host, &result);
+        summary.record(host, &result);
+    }
+
+    println!("==============================");
+    println!(
+        "{} succeeded, {} failed, {} timed out",
+        summary.succeeded.len(),
+        summary.failed.len(),
+        summary.timed_out.len()
+    );
+    if !summary.failed.is_empty() {
+      println!("failed hosts: {}", summary.failed.join(", "));
+    }
+    if !summary.timed_out.is_empty() {
+      println!("timed out hosts: {}", summary.timed_out.join(", "));
+    }
+    if summary.exit_code() != 0 {
+      std::process::exit(summary.exit_code());
+    }
+
     Ok(())
 }
 
-fn get_hosts(path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+// both run up to `parallel` hosts at once, `ordered` just hands them back in input order
+fn run_all<F, Fut>(
+    hosts: Vec<Host>,
+    parallel: usize,
+    ordered: bool,
+    run: F,
+) -> BoxStream<'static, (String, Result<CommandResult, MyError>)>
+where
+    F: Fn(Host) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<CommandResult, MyError>> + Send + 'static,
+{
+    let fibers = stream::iter(hosts).map(move |host| {
+        let name = host.name.clone();
+        run(host).map(move |x| (name, x))
+    });
+    if ordered {
+        fibers.buffered(parallel).boxed()
+    } else {
+        fibers.buffer_unordered(parallel).boxed()
+    }
+}
+
+fn show_exit_code(code: Option<i32>) -> String {
+    code.map_or("signal".to_string(), |x| x.to_string())
+}
+
+fn get_inventory(path: &str) -> Result<Inventory, Box<dyn Error>> {
     let file = File::open(path)?;
     let mut buf_reader = BufReader::new(file);
     let mut contents = String::new();
     buf_reader.read_to_string(&mut contents)?;
-    let hosts: Hosts = serde_yaml::from_str(&contents)?;
-    let result: Vec<_> = hosts.hosts.into_iter().map(|x| x).collect();
-    Ok(result)
+    Ok(Inventory::from_yaml(&contents)?)
+}
+
+// retries with exponential backoff, a connect that hangs past `connect_timeout` counts as a failed attempt
+async fn connect(host: &Host, ssh: &SshOptions) -> Result<Session, MyError> {
+    let mut builder = SessionBuilder::default();
+    builder.known_hosts_check(ssh.known_hosts.clone());
+    if let Some(user) = &host.vars.user {
+        builder.user(user.clone());
+    }
+    if let Some(port) = host.vars.port {
+        builder.port(port);
+    }
+    if let Some(identity_file) = &host.vars.identity_file {
+        builder.keyfile(identity_file);
+    }
+    let mut attempt = 0;
+    loop {
+        let result = match timeout(ssh.connect_timeout, builder.connect(host.address())).await {
+            Ok(x) => x.map_err(MyError::from),
+            Err(_) => Err(MyError::ConnectTimeout(ssh.connect_timeout)),
+        };
+        match result {
+            Err(e) if attempt < ssh.retries => {
+                let delay = RETRY_BACKOFF
+                    .checked_mul(2u32.saturating_pow(attempt))
+                    .map_or(MAX_RETRY_BACKOFF, |x| x.min(MAX_RETRY_BACKOFF));
+                eprintln!("{}: connect failed ({:?}), retrying in {:.1?}", host.name, e, delay);
+                sleep(delay).await;
+                attempt += 1;
+            }
+            x => return x,
+        }
+    }
 }
 
-async fn run_cmd(cmd: String, host: String) -> Result<String, MyError> {
+async fn run_cmd(host: Host, cmd: String, ssh: SshOptions) -> Result<CommandResult, MyError> {
     let handler = tokio::spawn(async move {
-        let session = Session::connect(host.clone(), KnownHosts::Accept).await?;
-        let child = session.shell(cmd).output().await?;
-        let r = String::from_utf8(child.stdout)?;
-        Ok(r)
+        let started = Instant::now();
+        let session = connect(&host, &ssh).await?;
+        let mut command = session.shell(cmd);
+        let output = command.output();
+        let child = match ssh.command_timeout {
+            Some(d) => timeout(d, output).await.map_err(|_| MyError::CommandTimeout(d))??,
+            None => output.await?,
+        };
+        CommandResult::from_output(child, started.elapsed())
     });
     handler.await?
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(xs: &[&str]) -> Vec<String> {
+        std::iter::once("multi-ssh").chain(xs.iter().copied()).map(String::from).collect()
+    }
+
+    fn result(exit_code: Option<i32>) -> Result<CommandResult, MyError> {
+        Ok(CommandResult {
+            stdout: String::new(),
+            stderr: String::new(),
+            exit_code,
+            duration: Duration::ZERO,
+        })
+    }
+
+    #[test]
+    fn local_command_result() {
+        let output = std::process::Command::new("sh")
+            .args(["-c", "echo out; echo err >&2; exit 3"])
+            .output()
+            .unwrap();
+        let r = CommandResult::from_output(output, Duration::ZERO).unwrap();
+        assert_eq!(r.stdout, "out\n");
+        assert_eq!(r.stderr, "err\n");
+        assert_eq!(r.exit_code, Some(3));
+        assert!(!r.success());
+        assert_eq!(show_exit_code(r.exit_code), "3");
+        assert_eq!(show_exit_code(None), "signal");
+    }
+
+    #[test]
+    fn summary_exit_code() {
+        let mut summary = Summary::default();
+        summary.record("a".to_string(), &result(Some(0)));
+        assert_eq!(summary.exit_code(), 0);
+        summary.record("b".to_string(), &result(Some(1)));
+        summary.record("c".to_string(), &result(None));
+        summary.record("d".to_string(), &Err(MyError::OpenSshError(openssh::Error::Disconnected)));
+        summary.record("e".to_string(), &Err(MyError::CommandTimeout(Duration::from_secs(1))));
+        summary.record("f".to_string(), &Err(MyError::ConnectTimeout(Duration::from_secs(1))));
+        assert_eq!(summary.succeeded, ["a"]);
+        assert_eq!(summary.failed, ["b", "c", "d"]);
+        assert_eq!(summary.timed_out, ["e", "f"]);
+        assert_eq!(summary.exit_code(), 1);
+
+        let mut summary = Summary::default();
+        summary.record("a".to_string(), &Err(MyError::ConnectTimeout(Duration::from_secs(1))));
+        assert_eq!(summary.exit_code(), 1);
+    }
+
+    #[tokio::test]
+    async fn ordered_output_keeps_inventory_order() {
+        let inventory = Inventory::from_yaml(
+            "
+web:
+  hosts:
+    web-01:
+    web-02:
+    web-03:
+db:
+  hosts: [db-01]
+",
+        )
+        .unwrap();
+        let hosts = inventory.limit("web:!web-02");
+        // the first host finishes last
+        let run = |host: Host| async move {
+            let delay = if host.name == "web-01" { 50 } else { 1 };
+            sleep(Duration::from_millis(delay)).await;
+            result(Some(0))
+        };
+        let names = |results: Vec<(String, Result<CommandResult, MyError>)>| {
+            results.into_iter().map(|x| x.0).collect::<Vec<_>>()
+        };
+        let results = run_all(hosts.clone(), 3, true, run).collect::<Vec<_>>().await;
+        assert_eq!(names(results), ["web-01", "web-03"]);
+        let results = run_all(hosts, 3, false, run).collect::<Vec<_>>().await;
+        assert_eq!(names(results), ["web-03", "web-01"]);
+    }
+
+    #[test]
+    fn ssh_defaults() {
+        let options = parse_args(&args(&["hosts.yaml", "uptime"])).unwrap();
+        assert_eq!(options.ssh.connect_timeout, DEFAULT_CONNECT_TIMEOUT);
+        assert_eq!(options.ssh.command_timeout, Some(DEFAULT_COMMAND_TIMEOUT));
+        assert_eq!(options.ssh.retries, DEFAULT_RETRIES);
+        assert!(matches!(options.ssh.known_hosts, KnownHosts::Accept));
+    }
+
+    #[test]
+    fn timeout_args() {
+        let options = parse_args(&args(&["--connect-timeout", "2.5", "-t", "30", "hosts.yaml", "uptime"])).unwrap();
+        assert_eq!(options.ssh.connect_timeout, Duration::from_millis(2500));
+        assert_eq!(options.ssh.command_timeout, Some(Duration::from_secs(30)));
+        let options = parse_args(&args(&["--timeout", "0", "hosts.yaml", "uptime"])).unwrap();
+        assert_eq!(options.ssh.command_timeout, None);
+        assert!(parse_args(&args(&["--connect-timeout", "0", "hosts.yaml", "uptime"])).is_err());
+        assert!(parse_args(&args(&["--timeout", "-1", "hosts.yaml", "uptime"])).is_err());
+        assert!(parse_args(&args(&["--timeout", "inf", "hosts.yaml", "uptime"])).is_err());
+        assert!(parse_args(&args(&["hosts.yaml", "uptime", "--timeout"])).is_err());
+    }
+
+    #[test]
+    fn retry_args() {
+        let options = parse_args(&args(&["--retries", "0", "hosts.yaml", "uptime"])).unwrap();
+        assert_eq!(options.ssh.retries, 0);
+        let options = parse_args(&args(&["--retries", "10", "hosts.yaml", "uptime"])).unwrap();
+        assert_eq!(options.ssh.retries, MAX_RETRIES);
+        assert!(parse_args(&args(&["--retries", "11", "hosts.yaml", "uptime"])).is_err());
+        assert!(parse_args(&args(&["--retries", "-1", "hosts.yaml", "uptime"])).is_err());
+    }
+
+    #[test]
+    fn known_hosts_args() {
+        let options = parse_args(&args(&["--known-hosts", "strict", "hosts.yaml", "uptime"])).unwrap();
+        assert!(matches!(options.ssh.known_hosts, KnownHosts::Strict));
+        let options = parse_args(&args(&["--known-hosts", "add", "hosts.yaml", "uptime"])).unwrap();
+        assert!(matches!(options.ssh.known_hosts, KnownHosts::Add));
+        assert!(parse_args(&args(&["--known-hosts", "yes", "hosts.yaml", "uptime"])).is_err());
+    }
+}
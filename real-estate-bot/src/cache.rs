@@ -0,0 +1,291 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::estate::{ArticleQuery, Complex, ComplexArticle, Error, EstateService, Region};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheStorage {
+    Memory,
+    // one json file per key, survives between runs
+    Disk(PathBuf),
+}
+
+// a zero ttl turns caching off for that method
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheConfig {
+    pub storage: CacheStorage,
+    pub region_ttl: Duration,
+    pub complex_ttl: Duration,
+    pub article_ttl: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            storage: CacheStorage::Memory,
+            region_ttl: Duration::from_secs(7 * 24 * 60 * 60),
+            complex_ttl: Duration::from_secs(24 * 60 * 60),
+            article_ttl: Duration::ZERO,
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn disabled() -> Self {
+        CacheConfig {
+            region_ttl: Duration::ZERO,
+            complex_ttl: Duration::ZERO,
+            article_ttl: Duration::ZERO,
+            ..Self::default()
+        }
+    }
+
+    pub fn storage(mut self, storage: CacheStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    pub fn region_ttl(mut self, ttl: Duration) -> Self {
+        self.region_ttl = ttl;
+        self
+    }
+
+    pub fn complex_ttl(mut self, ttl: Duration) -> Self {
+        self.complex_ttl = ttl;
+        self
+    }
+
+    pub fn article_ttl(mut self, ttl: Duration) -> Self {
+        self.article_ttl = ttl;
+        self
+    }
+}
+
+#[derive(Debug, Clone, serde_derive::Serialize, serde_derive::Deserialize)]
+struct CacheEntry {
+    stored_at: DateTime<Utc>,
+    value: serde_json::Value,
+}
+
+impl CacheEntry {
+    fn is_fresh(&self, ttl: Duration) -> bool {
+        chrono::Duration::from_std(ttl).is_ok_and(|ttl| Utc::now() - self.stored_at < ttl)
+    }
+}
+
+// wraps any `EstateService`, responses are cached per method and arguments
+#[derive(Debug, Clone)]
+pub struct CachedEstateService<T> {
+    inner: Arc<T>,
+    config: CacheConfig,
+    memory: Arc<Mutex<HashMap<String, CacheEntry>>>,
+}
+
+impl<T: EstateService> CachedEstateService<T> {
+    pub fn new(inner: Arc<T>, config: CacheConfig) -> Self {
+        CachedEstateService {
+            inner,
+            config,
+            memory: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn path(dir: &Path, key: &str) -> PathBuf {
+        let name = key
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        dir.join(format!("{}.json", name))
+    }
+
+    // an unreadable entry is treated as a miss
+    fn load(&self, key: &str) -> Option<CacheEntry> {
+        match &self.config.storage {
+            CacheStorage::Memory => self.memory.lock().unwrap().get(key).cloned(),
+            CacheStorage::Disk(dir) => {
+                serde_json::from_str(&fs::read_to_string(Self::path(dir, key)).ok()?).ok()
+            }
+        }
+    }
+
+    fn store(&self, key: String, entry: CacheEntry) -> Result<(), Error> {
+        match &self.config.storage {
+            CacheStorage::Memory => {
+                self.memory.lock().unwrap().insert(key, entry);
+            }
+            // written to a temp file and renamed so concurrent writers never leave half a file
+            CacheStorage::Disk(dir) => {
+                fs::create_dir_all(dir)?;
+                let path = Self::path(dir, &key);
+                let tmp = path.with_extension(format!("{:016x}.tmp", rand::random::<u64>()));
+                fs::write(&tmp, serde_json::to_string(&entry)?)?;
+                if let Err(e) = fs::rename(&tmp, &path) {
+                    let _ = fs::remove_file(&tmp);
+                    return Err(e.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn cached<R, F>(&self, key: String, ttl: Duration, fetch: F) -> Result<R, Error>
+    where
+        R: Serialize + DeserializeOwned,
+        F: Future<Output = Result<R, Error>>,
+    {
+        if ttl.is_zero() {
+            return fetch.await;
+        }
+        if let Some(entry) = self.load(&key).filter(|x| x.is_fresh(ttl)) {
+            if let Ok(x) = serde_json::from_value(entry.value) {
+                return Ok(x);
+            }
+        }
+        let x = fetch.await?;
+        let entry = CacheEntry {
+            stored_at: Utc::now(),
+            value: serde_json::to_value(&x)?,
+        };
+        // the fetch succeeded, a cache that can't be written only costs the next call
+        if let Err(e) = self.store(key.clone(), entry) {
+            eprintln!("cache: {}: {}", key, e);
+        }
+        Ok(x)
+    }
+}
+
+#[async_trait]
+impl<T: EstateService> EstateService for CachedEstateService<T> {
+    async fn region_list(self: Arc<Self>, parent_region_id: String) -> Result<Vec<Region>, Error> {
+        let key = format!("region_list/{}", parent_region_id);
+        let fetch = self.inner.clone().region_list(parent_region_id);
+        self.cached(key, self.config.region_ttl, fetch).await
+    }
+
+    async fn complex_list(self: Arc<Self>, region_id: String) -> Result<Vec<Complex>, Error> {
+        let key = format!("complex_list/{}", region_id);
+        let fetch = self.inner.clone().complex_list(region_id);
+        self.cached(key, self.config.complex_ttl, fetch).await
+    }
+
+    async fn complex_article_list(
+        self: Arc<Self>,
+        complex_id: String,
+        query: ArticleQuery,
+    ) -> Result<Vec<ComplexArticle>, Error> {
+        let key = format!(
+            "complex_article_list/{}?{}",
+            complex_id,
+            query.query_string()
+        );
+        let fetch = self.inner.clone().complex_article_list(complex_id, query);
+        self.cached(key, self.config.article_ttl, fetch).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::EstateServiceFake;
+
+    fn fake() -> EstateServiceFake {
+        EstateServiceFake::new()
+            .with_regions(
+                "1100000000",
+                vec![Region {
+                    cortar_no: "1168000000".to_string(),
+                    ..Default::default()
+                }],
+            )
+            .with_complexes(
+                "1168010300",
+                vec![Complex {
+                    hscp_no: "8928".to_string(),
+                    ..Default::default()
+                }],
+            )
+    }
+
+    #[tokio::test]
+    async fn memory_cache_per_method() {
+        let inner = fake();
+        let service = Arc::new(CachedEstateService::new(
+            Arc::new(inner.clone()),
+            CacheConfig::default().complex_ttl(Duration::ZERO),
+        ));
+        for _ in 0..2 {
+            let xs = service
+                .clone()
+                .region_list("1100000000".to_string())
+                .await
+                .unwrap();
+            assert_eq!(xs[0].cortar_no, "1168000000");
+            service
+                .clone()
+                .complex_list("1168010300".to_string())
+                .await
+                .unwrap();
+        }
+        // regions are cached, complexes with a zero ttl are not
+        assert_eq!(inner.calls().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn disk_cache_survives_instances() {
+        let dir =
+            std::env::temp_dir().join(format!("real-estate-bot-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let inner = fake();
+        let config = CacheConfig::default().storage(CacheStorage::Disk(dir.clone()));
+        for _ in 0..2 {
+            let service = Arc::new(CachedEstateService::new(
+                Arc::new(inner.clone()),
+                config.clone(),
+            ));
+            let xs = service
+                .complex_list("1168010300".to_string())
+                .await
+                .unwrap();
+            assert_eq!(xs[0].hscp_no, "8928");
+        }
+        assert_eq!(inner.calls().len(), 1);
+
+        let service = Arc::new(CachedEstateService::new(
+            Arc::new(inner.clone()),
+            CacheConfig::disabled().storage(CacheStorage::Disk(dir.clone())),
+        ));
+        service
+            .complex_list("1168010300".to_string())
+            .await
+            .unwrap();
+        assert_eq!(inner.calls().len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn store_failure_still_returns_fetch() {
+        // a regular file where the cache directory should be
+        let dir =
+            std::env::temp_dir().join(format!("real-estate-bot-cache-file-{}", std::process::id()));
+        fs::write(&dir, "").unwrap();
+        let service = Arc::new(CachedEstateService::new(
+            Arc::new(fake()),
+            CacheConfig::default().storage(CacheStorage::Disk(dir.clone())),
+        ));
+        let xs = service
+            .complex_list("1168010300".to_string())
+            .await
+            .unwrap();
+        assert_eq!(xs[0].hscp_no, "8928");
+        fs::remove_file(&dir).unwrap();
+    }
+}
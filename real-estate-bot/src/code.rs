@@ -0,0 +1,146 @@
+// upstream code fields as enums, a code we don't know yet lands in `Unknown` instead of
+// failing the whole response
+macro_rules! code_enum {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident => $code:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum $name {
+            $($variant,)*
+            Unknown(String),
+        }
+
+        impl $name {
+            pub fn code(&self) -> &str {
+                match self {
+                    $($name::$variant => $code,)*
+                    $name::Unknown(x) => x,
+                }
+            }
+
+            pub fn from_code(code: &str) -> Self {
+                match code {
+                    $($code => $name::$variant,)*
+                    _ => $name::Unknown(code.to_string()),
+                }
+            }
+
+            pub fn is_unknown(&self) -> bool {
+                matches!(self, $name::Unknown(_))
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                $name::Unknown(String::new())
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.code())
+            }
+        }
+
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.code())
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let code = <String as serde::Deserialize>::deserialize(deserializer)?;
+                Ok($name::from_code(&code))
+            }
+        }
+    };
+}
+
+pub(crate) use code_enum;
+
+#[derive(serde_derive::Deserialize)]
+#[serde(untagged)]
+enum YnValue {
+    Bool(bool),
+    Str(String),
+}
+
+impl YnValue {
+    fn is_yes(&self) -> bool {
+        match self {
+            YnValue::Bool(x) => *x,
+            YnValue::Str(x) => x.trim().eq_ignore_ascii_case("Y"),
+        }
+    }
+}
+
+// "Y" / "N" flags as bool, written back as "Y" / "N", anything but "Y" or true reads as false
+pub mod yn {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(x: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(if *x { "Y" } else { "N" })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+        Ok(super::YnValue::deserialize(deserializer)?.is_yes())
+    }
+}
+
+// same as `yn` for optional fields, null stays None
+pub mod yn_option {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(x: &Option<bool>, serializer: S) -> Result<S::Ok, S::Error> {
+        match x {
+            Some(x) => super::yn::serialize(x, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<bool>, D::Error> {
+        Ok(Option::<super::YnValue>::deserialize(deserializer)?.map(|x| x.is_yes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    code_enum!(Color {
+        Red => "R",
+        Blue => "B",
+    });
+
+    #[derive(Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+    struct Flags {
+        color: Color,
+        #[serde(with = "super::yn")]
+        more: bool,
+        #[serde(default, with = "super::yn_option")]
+        done: Option<bool>,
+    }
+
+    #[test]
+    fn tolerant_codes_and_flags() {
+        let x: Flags =
+            serde_json::from_str(r#"{"color": "G", "more": "y", "done": null}"#).unwrap();
+        assert_eq!(
+            x,
+            Flags {
+                color: Color::Unknown("G".to_string()),
+                more: true,
+                done: None,
+            }
+        );
+        let x: Flags = serde_json::from_str(r#"{"color": "R", "more": "N", "done": "Y"}"#).unwrap();
+        assert_eq!(x.color, Color::Red);
+        assert!(!x.more);
+        assert_eq!(
+            serde_json::to_string(&x).unwrap(),
+            r#"{"color":"R","more":"N","done":"Y"}"#
+        );
+        assert!(Color::Unknown("G".to_string()).is_unknown());
+        assert_eq!(Color::from_code("B").to_string(), "B");
+    }
+}
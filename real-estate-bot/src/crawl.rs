@@ -0,0 +1,256 @@
+use futures::{
+    stream::{self, StreamExt},
+    FutureExt, TryFutureExt,
+};
+use std::sync::Arc;
+
+use crate::{
+    estate::{ArticleQuery, Complex, Error, EstateService, ListingWithComplex},
+    region::crawl_region_tree,
+};
+
+#[derive(Debug)]
+pub struct CrawlFailure {
+    pub complex_id: String,
+    pub error: Error,
+}
+
+// a dong whose complex list could not be fetched during a recursive crawl
+#[derive(Debug)]
+pub struct RegionFailure {
+    pub region_no: String,
+    pub error: Error,
+}
+
+#[derive(Debug, Default)]
+pub struct CrawlReport {
+    pub entries: Vec<ListingWithComplex>,
+    pub failures: Vec<CrawlFailure>,
+    pub region_failures: Vec<RegionFailure>,
+}
+
+impl CrawlReport {
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty() && self.region_failures.is_empty()
+    }
+
+    pub fn failed_complex_ids(&self) -> Vec<String> {
+        self.failures.iter().map(|x| x.complex_id.clone()).collect()
+    }
+
+    pub fn failed_region_nos(&self) -> Vec<String> {
+        self.region_failures
+            .iter()
+            .map(|x| x.region_no.clone())
+            .collect()
+    }
+
+    pub fn merge(&mut self, other: CrawlReport) {
+        self.entries.extend(other.entries);
+        self.failures.extend(other.failures);
+        self.region_failures.extend(other.region_failures);
+    }
+}
+
+fn result_flatten<T, E: From<E1>, E1>(r: Result<Result<T, E>, E1>) -> Result<T, E> {
+    r.map_err(|e| e.into()).and_then(|y| y)
+}
+
+// a failing complex is recorded in the report instead of failing the whole crawl,
+// only the complex list itself is all-or-nothing
+pub async fn get_result_list<T: EstateService>(
+    service: Arc<T>,
+    region_no: String,
+    query: ArticleQuery,
+    concurrency: usize,
+) -> Result<CrawlReport, Error> {
+    let xs = service.clone().complex_list(region_no).await?;
+    let results = stream::iter(xs)
+        .map(|x| {
+            let hscp_no = x.hscp_no.clone();
+            let articles = service
+                .clone()
+                .complex_article_list(x.hscp_no.clone(), query.clone())
+                .map_ok(move |xs| {
+                    xs.into_iter()
+                        .map(|y| ListingWithComplex::new(x.clone(), y))
+                        .collect::<Vec<_>>()
+                });
+            tokio::spawn(articles).map(|r| (hscp_no, result_flatten(r)))
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+    let mut report = CrawlReport::default();
+    for (complex_id, r) in results {
+        match r {
+            Ok(xs) => report.entries.extend(xs),
+            Err(error) => report.failures.push(CrawlFailure { complex_id, error }),
+        }
+    }
+    Ok(report)
+}
+
+// with `recursive` every dong under region_no is crawled, otherwise region_no itself is the dong.
+// a dong whose complex list fails is recorded in the report and the other dongs are still crawled,
+// so is a region above the dong level that listed no sub-regions
+pub async fn crawl_region<T: EstateService>(
+    service: Arc<T>,
+    region_no: String,
+    recursive: bool,
+    query: ArticleQuery,
+    concurrency: usize,
+) -> Result<CrawlReport, Error> {
+    if !recursive {
+        return get_result_list(service, region_no, query, concurrency).await;
+    }
+    let tree = crawl_region_tree(service.clone(), region_no, concurrency).await?;
+    let mut report = CrawlReport::default();
+    report
+        .region_failures
+        .extend(tree.dead_ends().iter().map(|x| RegionFailure {
+            region_no: x.cortar_no.clone(),
+            error: Error::EmptyRegion(x.cortar_no.clone()),
+        }));
+    for region_no in tree.leaves().iter().map(|x| x.cortar_no.clone()) {
+        match get_result_list(
+            service.clone(),
+            region_no.clone(),
+            query.clone(),
+            concurrency,
+        )
+        .await
+        {
+            Ok(x) => report.merge(x),
+            Err(error) => report
+                .region_failures
+                .push(RegionFailure { region_no, error }),
+        }
+    }
+    Ok(report)
+}
+
+// complexes of region_no, or of every dong under it with `recursive`.
+// fails when any of them can't be listed, a region that listed no sub-regions included
+pub async fn crawl_complexes<T: EstateService>(
+    service: Arc<T>,
+    region_no: String,
+    recursive: bool,
+    concurrency: usize,
+) -> Result<Vec<Complex>, Error> {
+    let regions = if recursive {
+        let tree = crawl_region_tree(service.clone(), region_no, concurrency).await?;
+        if let Some(x) = tree.dead_ends().first() {
+            return Err(Error::EmptyRegion(x.cortar_no.clone()));
+        }
+        tree.leaves().iter().map(|x| x.cortar_no.clone()).collect()
+    } else {
+        vec![region_no]
+    };
+    let xs = stream::iter(regions)
+        .map(|x| service.clone().complex_list(x))
+        .buffered(concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+    Ok(xs
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        estate::{ComplexArticle, CortarType, Region},
+        fake::{EstateServiceFake, FakeCall},
+    };
+
+    fn complex(hscp_no: &str) -> Complex {
+        Complex {
+            hscp_no: hscp_no.to_string(),
+            cortar_no: "4113110800".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn article(atcl_no: &str) -> ComplexArticle {
+        ComplexArticle {
+            atcl_no: Some(atcl_no.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn partial_failure() {
+        let service = EstateServiceFake::new()
+            .with_complexes("4113110800", vec![complex("8928"), complex("8929")])
+            .with_articles("8928", vec![article("1"), article("2")])
+            .with_articles("8929", vec![article("3")])
+            .fail_always(FakeCall::article_page("8929", 1));
+        let report = get_result_list(
+            Arc::new(service),
+            "4113110800".to_string(),
+            ArticleQuery::default(),
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.entries.len(), 2);
+        assert!(report.entries.iter().all(|x| x.complex.hscp_no == "8928"));
+        assert_eq!(report.failed_complex_ids(), vec!["8929"]);
+        assert!(!report.is_complete());
+    }
+
+    #[tokio::test]
+    async fn recursive_crawl_keeps_other_dongs() {
+        let dong = |cortar_no: &str| Region {
+            cortar_no: cortar_no.to_string(),
+            cortar_type: CortarType::Section,
+            ..Default::default()
+        };
+        let service = EstateServiceFake::new()
+            .with_regions(
+                "4113100000",
+                vec![
+                    dong("4113110800"),
+                    dong("4113110900"),
+                    Region {
+                        cortar_type: CortarType::Division,
+                        ..dong("4113199999")
+                    },
+                ],
+            )
+            .with_complexes("4113110800", vec![complex("8928")])
+            .with_articles("8928", vec![article("1")])
+            .fail_always(FakeCall::ComplexList("4113110900".to_string()));
+        let report = crawl_region(
+            Arc::new(service),
+            "4113100000".to_string(),
+            true,
+            ArticleQuery::default(),
+            2,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.entries.len(), 1);
+        assert_eq!(report.failed_region_nos(), vec!["4113199999", "4113110900"]);
+        assert!(!report.is_complete());
+    }
+
+    #[tokio::test]
+    async fn complex_list_failure_fails_crawl() {
+        let service =
+            EstateServiceFake::new().fail_always(FakeCall::ComplexList("4113110800".to_string()));
+        let rs = get_result_list(
+            Arc::new(service),
+            "4113110800".to_string(),
+            ArticleQuery::default(),
+            4,
+        )
+        .await;
+        assert!(rs.is_err());
+    }
+}
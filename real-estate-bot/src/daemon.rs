@@ -0,0 +1,346 @@
+use chrono::Local;
+use rand::Rng;
+use std::{fs, future::Future, path::Path, str::FromStr, sync::Arc, time::Duration};
+use tokio::{
+    sync::{self, watch},
+    time,
+};
+
+use crate::{
+    crawl::crawl_region,
+    estate::{ArticleQuery, Error, EstateService, TradeType},
+    notify::{notify_diff, Notifier},
+    snapshot::{Snapshot, SnapshotDiff, SnapshotStore},
+};
+
+fn default_snapshot_path() -> String {
+    "snapshots.jsonl".to_string()
+}
+
+fn default_concurrency() -> usize {
+    8
+}
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct DaemonConfig {
+    #[serde(default = "default_snapshot_path")]
+    pub snapshot_path: String,
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    pub jobs: Vec<JobConfig>,
+}
+
+// schedule is a cron expression with seconds: "sec min hour day-of-month month day-of-week",
+// evaluated in the local time zone like the log timestamps
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct JobConfig {
+    pub name: String,
+    pub region: String,
+    #[serde(default)]
+    pub recursive: bool,
+    pub schedule: String,
+    #[serde(default)]
+    pub jitter_secs: u64,
+    #[serde(default)]
+    pub trade_types: Vec<String>,
+}
+
+impl DaemonConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let config: DaemonConfig = serde_yaml::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| Error::ConfigError(e.to_string()))?;
+        for job in config.jobs.iter() {
+            job.schedule()?;
+            job.query()?;
+        }
+        Ok(config)
+    }
+}
+
+impl JobConfig {
+    pub fn schedule(&self) -> Result<cron::Schedule, Error> {
+        cron::Schedule::from_str(&self.schedule)
+            .map_err(|e| Error::ConfigError(format!("{}: {}", self.name, e)))
+    }
+
+    pub fn query(&self) -> Result<ArticleQuery, Error> {
+        if self.trade_types.is_empty() {
+            return Ok(ArticleQuery::all());
+        }
+        let trade_types = self
+            .trade_types
+            .iter()
+            .map(|x| x.parse::<TradeType>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::ConfigError(format!("{}: {}", self.name, e)))?;
+        Ok(ArticleQuery::new().trade_types(trade_types))
+    }
+
+    fn jitter(&self) -> Duration {
+        if self.jitter_secs == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_millis(rand::thread_rng().gen_range(0..self.jitter_secs * 1000))
+        }
+    }
+}
+
+pub struct Daemon<T> {
+    service: Arc<T>,
+    notifier: Option<Arc<dyn Notifier>>,
+    // held while recording so two jobs don't interleave their writes
+    store: sync::Mutex<SnapshotStore>,
+    config: DaemonConfig,
+}
+
+impl<T: EstateService> Daemon<T> {
+    pub fn new(service: Arc<T>, notifier: Option<Arc<dyn Notifier>>, config: DaemonConfig) -> Self {
+        Daemon {
+            service,
+            notifier,
+            store: sync::Mutex::new(SnapshotStore::new(&config.snapshot_path)),
+            config,
+        }
+    }
+
+    // crawls, stores and notifies once, returns the changes since the previous run
+    pub async fn run_job(&self, job: &JobConfig) -> Result<Option<SnapshotDiff>, Error> {
+        let report = crawl_region(
+            self.service.clone(),
+            job.region.clone(),
+            job.recursive,
+            job.query()?,
+            self.config.concurrency,
+        )
+        .await?;
+        for failure in report.failures.iter() {
+            eprintln!(
+                "[{}] {}: {}: {}",
+                Local::now(),
+                job.name,
+                failure.complex_id,
+                failure.error
+            );
+        }
+        for failure in report.region_failures.iter() {
+            eprintln!(
+                "[{}] {}: region {}: {}",
+                Local::now(),
+                job.name,
+                failure.region_no,
+                failure.error
+            );
+        }
+        let snapshot = Snapshot {
+            job: Some(job.name.clone()),
+            ..Snapshot::from_report(job.region.clone(), report)
+        };
+        let guard = self.store.lock().await;
+        let store = guard.clone();
+        let diff = tokio::task::spawn_blocking(move || store.record(&snapshot)).await??;
+        drop(guard);
+        if let (Some(diff), Some(notifier)) = (&diff, &self.notifier) {
+            notify_diff(notifier.as_ref(), diff).await?;
+        }
+        Ok(diff)
+    }
+
+    async fn job_loop(self: Arc<Self>, job: JobConfig, mut shutdown: watch::Receiver<bool>) {
+        let schedule = match job.schedule() {
+            Ok(x) => x,
+            Err(e) => return eprintln!("[{}] {}", Local::now(), e),
+        };
+        let running = Arc::new(sync::Mutex::new(()));
+        while let Some(next) = schedule.upcoming(Local).next() {
+            let delay = (next - Local::now()).to_std().unwrap_or_default() + job.jitter();
+            tokio::select! {
+                _ = time::sleep(delay) => {}
+                _ = shutdown.changed() => break,
+            }
+            // a run that outlives its interval makes the following ticks skip instead of pile up
+            let guard = match running.clone().try_lock_owned() {
+                Ok(x) => x,
+                Err(_) => {
+                    println!(
+                        "[{}] {}: previous run still in progress, skipped",
+                        Local::now(),
+                        job.name
+                    );
+                    continue;
+                }
+            };
+            let daemon = self.clone();
+            let job = job.clone();
+            tokio::spawn(async move {
+                let _guard = guard;
+                println!("[{}] {}: started", Local::now(), job.name);
+                match daemon.run_job(&job).await {
+                    Ok(Some(diff)) => println!(
+                        "[{}] {}: {} new, {} removed, {} price changed",
+                        Local::now(),
+                        job.name,
+                        diff.added.len(),
+                        diff.removed.len(),
+                        diff.price_changed.len()
+                    ),
+                    Ok(None) => println!("[{}] {}: first snapshot stored", Local::now(), job.name),
+                    Err(e) => eprintln!("[{}] {}: {}", Local::now(), job.name, e),
+                }
+            });
+        }
+        // let an in-flight run finish before returning
+        let _ = running.lock().await;
+    }
+
+    // runs every job on its schedule until `shutdown` resolves
+    pub async fn run<F: Future<Output = ()>>(self, shutdown: F) -> Result<(), Error> {
+        for job in self.config.jobs.iter() {
+            job.schedule()?;
+            job.query()?;
+        }
+        let daemon = Arc::new(self);
+        let (tx, rx) = watch::channel(false);
+        let handles = daemon
+            .config
+            .jobs
+            .iter()
+            .map(|job| tokio::spawn(daemon.clone().job_loop(job.clone(), rx.clone())))
+            .collect::<Vec<_>>();
+        shutdown.await;
+        println!("[{}] shutting down", Local::now());
+        let _ = tx.send(true);
+        for handle in handles {
+            handle.await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        estate::{Complex, ComplexArticle},
+        fake::EstateServiceFake,
+        notify::RecordingNotifier,
+    };
+
+    fn article(atcl_no: &str, prc_info: &str) -> ComplexArticle {
+        ComplexArticle {
+            atcl_no: Some(atcl_no.to_string()),
+            prc_info: Some(prc_info.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_config() {
+        let config: DaemonConfig = serde_yaml::from_str(
+            "
+jobs:
+  - name: changok
+    region: '4113110800'
+    schedule: '0 0 */3 * * *'
+    jitter_secs: 60
+    trade_types: [deal, B1]
+",
+        )
+        .unwrap();
+        assert_eq!(config.snapshot_path, "snapshots.jsonl");
+        let job = &config.jobs[0];
+        assert!(job.schedule().is_ok());
+        assert_eq!(
+            job.query().unwrap().trade_types,
+            vec![TradeType::Deal, TradeType::Lease]
+        );
+        assert!(JobConfig {
+            schedule: "every hour".to_string(),
+            ..job.clone()
+        }
+        .schedule()
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn run_job_notifies_changes() {
+        let path = std::env::temp_dir().join(format!(
+            "real-estate-bot-daemon-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(path.with_extension("latest.json"));
+        let service = EstateServiceFake::new()
+            .with_complexes(
+                "4113110800",
+                vec![Complex {
+                    hscp_no: "8928".to_string(),
+                    ..Default::default()
+                }],
+            )
+            .with_articles("8928", vec![article("1", "12억")]);
+        let notifier = RecordingNotifier::new();
+        let job = JobConfig {
+            name: "changok".to_string(),
+            region: "4113110800".to_string(),
+            recursive: false,
+            schedule: "0 * * * * *".to_string(),
+            jitter_secs: 0,
+            trade_types: Vec::new(),
+        };
+        let daemon = Daemon::new(
+            Arc::new(service.clone()),
+            Some(Arc::new(notifier.clone())),
+            DaemonConfig {
+                snapshot_path: path.to_string_lossy().to_string(),
+                concurrency: 2,
+                jobs: vec![job.clone()],
+            },
+        );
+        assert_eq!(daemon.run_job(&job).await.unwrap(), None);
+
+        // the fake shares its state between clones
+        let _ = service.with_articles("8928", vec![article("1", "11억"), article("2", "9억")]);
+        let diff = daemon.run_job(&job).await.unwrap().unwrap();
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.price_changed.len(), 1);
+        assert_eq!(notifier.messages().len(), 2);
+
+        // a second job on the same region keeps its own series
+        let leases = JobConfig {
+            name: "changok-leases".to_string(),
+            trade_types: vec!["lease".to_string()],
+            ..job.clone()
+        };
+        assert_eq!(daemon.run_job(&leases).await.unwrap(), None);
+        assert!(daemon.run_job(&job).await.unwrap().unwrap().is_empty());
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("latest.json")).unwrap();
+    }
+
+    #[tokio::test]
+    async fn run_stops_on_shutdown() {
+        let daemon = Daemon::new(
+            Arc::new(EstateServiceFake::new()),
+            None,
+            DaemonConfig {
+                snapshot_path: default_snapshot_path(),
+                concurrency: 1,
+                jobs: vec![JobConfig {
+                    name: "yearly".to_string(),
+                    region: "4113110800".to_string(),
+                    recursive: false,
+                    schedule: "0 0 0 1 1 *".to_string(),
+                    jitter_secs: 0,
+                    trade_types: Vec::new(),
+                }],
+            },
+        );
+        time::timeout(
+            Duration::from_secs(5),
+            daemon.run(time::sleep(Duration::from_millis(10))),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    }
+}
@@ -1,9 +1,13 @@
 use async_trait::async_trait;
-use futures::{
-    stream::{self, StreamExt},
-    FutureExt,
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use chrono::NaiveDate;
+use std::{fmt, str::FromStr, sync::Arc, time::Duration};
+use tokio::time;
+
+use crate::{
+    code::{code_enum, yn, yn_option},
+    retry::{RateLimiter, RetryPolicy},
 };
-use std::{ops::Deref, sync::Arc};
 
 // https://m.land.naver.com/map/getRegionList?cortarNo=1168000000&mycortarNo=
 #[derive(Default, Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
@@ -31,7 +35,7 @@ pub struct Region {
     #[serde(rename = "MapYCrdn")]
     pub map_ycrdn: String,
     #[serde(rename = "CortarType")]
-    pub cortar_type: String,
+    pub cortar_type: CortarType,
 }
 
 // https://m.land.naver.com/complex/ajax/complexListByCortarNo?cortarNo=1168010300
@@ -41,8 +45,8 @@ pub struct ComplexListResponse {
     pub result: Vec<Complex>,
     pub sec_info: Region,
     pub dvsn_info: Region,
-    #[serde(rename = "loginYN")]
-    pub login_yn: String,
+    #[serde(rename = "loginYN", with = "yn")]
+    pub login_yn: bool,
     pub city_info: Region,
 }
 
@@ -51,7 +55,7 @@ pub struct ComplexListResponse {
 pub struct Complex {
     pub hscp_no: String,
     pub hscp_nm: String,
-    pub hscp_type_cd: String,
+    pub hscp_type_cd: ComplexType,
     pub hscp_type_nm: String,
     pub lat: String,
     pub lng: String,
@@ -75,7 +79,8 @@ pub struct ComplexArticleListResponse {
 pub struct ComplexArticleListResult {
     pub list: Vec<ComplexArticle>,
     pub tot_atcl_cnt: i64,
-    pub more_data_yn: String,
+    #[serde(with = "yn")]
+    pub more_data_yn: bool,
     pub show_guarantee: bool,
 }
 
@@ -85,12 +90,12 @@ pub struct ComplexArticle {
     pub rep_img_url: Option<String>,
     pub atcl_no: Option<String>,
     pub rep_img_tp_cd: Option<String>,
-    pub vrfc_tp_cd: Option<String>,
+    pub vrfc_tp_cd: Option<VerificationType>,
     pub atcl_nm: Option<String>,
     pub bild_nm: Option<String>,
-    pub trad_tp_cd: Option<String>,
+    pub trad_tp_cd: Option<TradeType>,
     pub trad_tp_nm: Option<String>,
-    pub rlet_tp_cd: Option<String>,
+    pub rlet_tp_cd: Option<RealEstateType>,
     pub rlet_tp_nm: Option<String>,
     pub spc1: Option<String>,
     pub spc2: Option<String>,
@@ -103,14 +108,16 @@ pub struct ComplexArticle {
     pub same_addr_hash: Option<String>,
     pub same_addr_max_prc: Option<String>,
     pub same_addr_min_prc: Option<String>,
-    pub trad_cmpl_yn: Option<String>,
+    #[serde(default, with = "yn_option")]
+    pub trad_cmpl_yn: Option<bool>,
     pub tag_list: Vec<Option<String>>,
-    pub atcl_stat_cd: Option<String>,
+    pub atcl_stat_cd: Option<ArticleStatus>,
     pub cpid: Option<String>,
     pub cp_nm: Option<String>,
     pub cp_cnt: i64,
     pub rltr_nm: Option<String>,
-    pub direct_trad_yn: Option<String>,
+    #[serde(default, with = "yn_option")]
+    pub direct_trad_yn: Option<bool>,
     pub direction: Option<String>,
     pub trade_price_han: Option<String>,
     pub trade_rent_price: i64,
@@ -118,9 +125,385 @@ pub struct ComplexArticle {
     pub trade_checked_by_owner: bool,
     pub point: i64,
     pub dtl_addr: Option<String>,
-    pub dtl_addr_yn: Option<String>,
+    #[serde(default, with = "yn_option")]
+    pub dtl_addr_yn: Option<bool>,
+}
+
+impl ComplexArticle {
+    pub fn price(&self) -> Option<Price> {
+        parse_price_opt(&self.prc_info)
+    }
+
+    pub fn same_addr_max_price(&self) -> Option<Price> {
+        parse_price_opt(&self.same_addr_max_prc)
+    }
+
+    pub fn same_addr_min_price(&self) -> Option<Price> {
+        parse_price_opt(&self.same_addr_min_prc)
+    }
+
+    pub fn trade_price(&self) -> Option<Price> {
+        parse_price_opt(&self.trade_price_han)
+    }
+
+    // 공급면적 (㎡)
+    pub fn supply_area(&self) -> Option<f64> {
+        self.spc1.as_deref().and_then(|x| x.trim().parse().ok())
+    }
+
+    // 전용면적 (㎡)
+    pub fn exclusive_area(&self) -> Option<f64> {
+        self.spc2.as_deref().and_then(|x| x.trim().parse().ok())
+    }
+
+    // flr_info looks like "12/25", "B1/15" or "고/25" when the exact floor is hidden
+    pub fn floor(&self) -> Option<i64> {
+        let floor = self.flr_info.as_deref()?.split('/').next()?.trim();
+        match floor.strip_prefix('B') {
+            Some(x) => x.parse::<i64>().ok().map(|x| -x),
+            None => floor.parse().ok(),
+        }
+    }
+
+    pub fn total_floors(&self) -> Option<i64> {
+        self.flr_info
+            .as_deref()?
+            .split('/')
+            .nth(1)?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    // cfmYmd comes as "21.02.01", sometimes with a trailing dot or a 4 digit year
+    pub fn confirmed_date(&self) -> Option<NaiveDate> {
+        let s = self.cfm_ymd.as_deref()?.trim().trim_end_matches('.');
+        ["%y.%m.%d", "%Y.%m.%d"]
+            .iter()
+            .find_map(|f| NaiveDate::parse_from_str(s, f).ok())
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+pub struct ListingKey {
+    pub hscp_no: String,
+    pub atcl_no: String,
+}
+
+// an article joined with the complex it was listed under
+#[derive(Default, Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListingWithComplex {
+    pub complex: Complex,
+    pub article: ComplexArticle,
+}
+
+impl ListingWithComplex {
+    pub fn new(complex: Complex, article: ComplexArticle) -> Self {
+        ListingWithComplex { complex, article }
+    }
+
+    pub fn key(&self) -> Option<ListingKey> {
+        self.article.atcl_no.as_ref().map(|atcl_no| ListingKey {
+            hscp_no: self.complex.hscp_no.clone(),
+            atcl_no: atcl_no.clone(),
+        })
+    }
+}
+
+fn parse_price_opt(s: &Option<String>) -> Option<Price> {
+    s.as_deref().and_then(|x| x.parse().ok())
+}
+
+// prices are in units of 10,000 won (만원): "12억 5,000" = 125000, "3억/120" = 30000 + 120/month
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+pub struct Price {
+    pub amount: BigDecimal,
+    pub monthly_rent: Option<BigDecimal>,
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+#[error("invalid price: {0:?}")]
+pub struct ParsePriceError(String);
+
+fn parse_man_won(s: &str) -> Option<BigDecimal> {
+    let s: String = s
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ',')
+        .collect();
+    let s = s.trim_end_matches('원').trim_end_matches('만');
+    if s.is_empty() {
+        return None;
+    }
+    match s.split_once('억') {
+        Some((eok, rest)) => {
+            let eok = BigDecimal::from_str(eok).ok()?;
+            let rest = if rest.is_empty() {
+                BigDecimal::zero()
+            } else {
+                BigDecimal::from_str(rest).ok()?
+            };
+            Some(eok * BigDecimal::from(10000) + rest)
+        }
+        None => BigDecimal::from_str(s).ok(),
+    }
+}
+
+fn format_man_won(v: &BigDecimal) -> String {
+    match v.to_i64().filter(|_| v.is_integer()) {
+        Some(x) if x >= 10000 && x % 10000 == 0 => format!("{}억", x / 10000),
+        Some(x) if x >= 10000 => format!("{}억 {}", x / 10000, with_commas(x % 10000)),
+        Some(x) => with_commas(x),
+        None => v.normalized().to_string(),
+    }
+}
+
+fn with_commas(x: i64) -> String {
+    let s = x.to_string();
+    let mut rs = String::new();
+    for (i, c) in s.chars().enumerate() {
+        if i > 0 && (s.len() - i).is_multiple_of(3) {
+            rs.push(',');
+        }
+        rs.push(c);
+    }
+    rs
+}
+
+impl Price {
+    pub fn new(amount: BigDecimal) -> Self {
+        Price {
+            amount,
+            monthly_rent: None,
+        }
+    }
+
+    pub fn with_monthly_rent(amount: BigDecimal, monthly_rent: BigDecimal) -> Self {
+        Price {
+            amount,
+            monthly_rent: Some(monthly_rent),
+        }
+    }
+
+    pub fn is_monthly_rent(&self) -> bool {
+        self.monthly_rent.is_some()
+    }
+
+    pub fn won(&self) -> BigDecimal {
+        &self.amount * BigDecimal::from(10000)
+    }
+}
+
+impl FromStr for Price {
+    type Err = ParsePriceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParsePriceError(s.to_string());
+        match s.split_once('/') {
+            Some((amount, monthly_rent)) => Ok(Price::with_monthly_rent(
+                parse_man_won(amount).ok_or_else(err)?,
+                parse_man_won(monthly_rent).ok_or_else(err)?,
+            )),
+            None => Ok(Price::new(parse_man_won(s).ok_or_else(err)?)),
+        }
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_man_won(&self.amount))?;
+        if let Some(monthly_rent) = &self.monthly_rent {
+            write!(f, "/{}", format_man_won(monthly_rent))?;
+        }
+        Ok(())
+    }
+}
+
+code_enum!(
+    // tradTpCd: A1 = deal_cnt, B1 = lease_cnt, B2 = rent_cnt
+    TradeType {
+        Deal => "A1",
+        Lease => "B1",
+        Rent => "B2",
+    }
+);
+
+impl TradeType {
+    pub const ALL: [TradeType; 3] = [TradeType::Deal, TradeType::Lease, TradeType::Rent];
+}
+
+code_enum!(
+    // CortarType: city > dvsn (gu) > sec (dong)
+    CortarType {
+        City => "city",
+        Division => "dvsn",
+        Section => "sec",
+    }
+);
+
+code_enum!(
+    // hscpTypeCd
+    ComplexType {
+        Apartment => "A01",
+        Officetel => "A02",
+    }
+);
+
+code_enum!(
+    // rletTpCd
+    RealEstateType {
+        Apartment => "A01",
+        Officetel => "A02",
+    }
+);
+
+code_enum!(
+    // atclStatCd
+    ArticleStatus {
+        Active => "R0",
+    }
+);
+
+code_enum!(
+    // vrfcTpCd: how the listing was verified
+    VerificationType {
+        Owner => "OWNER",
+        Document => "DOC",
+        Site => "SITE",
+        Mobile => "MOBL",
+    }
+);
+
+impl FromStr for TradeType {
+    type Err = String;
+
+    // accepts the upstream code, the english or the korean name
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "a1" | "deal" | "매매" => Ok(TradeType::Deal),
+            "b1" | "lease" | "전세" => Ok(TradeType::Lease),
+            "b2" | "rent" | "월세" => Ok(TradeType::Rent),
+            _ => Err(format!("unknown trade type: {}", s)),
+        }
+    }
+}
+
+impl Complex {
+    pub fn article_count(&self, trade_type: TradeType) -> i64 {
+        match trade_type {
+            TradeType::Deal => self.deal_cnt,
+            TradeType::Lease => self.lease_cnt,
+            TradeType::Rent => self.rent_cnt,
+            TradeType::Unknown(_) => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArticleOrder {
+    Rank,
+    Price,
+    Date,
+    Area,
+}
+
+impl ArticleOrder {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ArticleOrder::Rank => "rank",
+            ArticleOrder::Price => "price",
+            ArticleOrder::Date => "date",
+            ArticleOrder::Area => "area",
+        }
+    }
+}
+
+// defaults to the old hardcoded `tradTpCd=A1&order=price&showR0=N`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ArticleQuery {
+    pub trade_types: Vec<TradeType>,
+    pub order: ArticleOrder,
+    pub show_completed: bool,
+}
+
+impl Default for ArticleQuery {
+    fn default() -> Self {
+        ArticleQuery {
+            trade_types: vec![TradeType::Deal],
+            order: ArticleOrder::Price,
+            show_completed: false,
+        }
+    }
 }
 
+impl ArticleQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn all() -> Self {
+        Self::new().trade_types(TradeType::ALL.to_vec())
+    }
+
+    pub fn trade_types(mut self, trade_types: Vec<TradeType>) -> Self {
+        self.trade_types = trade_types;
+        self
+    }
+
+    pub fn trade_type(mut self, trade_type: TradeType) -> Self {
+        if !self.trade_types.contains(&trade_type) {
+            self.trade_types.push(trade_type);
+        }
+        self
+    }
+
+    pub fn order(mut self, order: ArticleOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn show_completed(mut self, show_completed: bool) -> Self {
+        self.show_completed = show_completed;
+        self
+    }
+
+    pub fn query_string(&self) -> String {
+        let trade_types = self
+            .trade_types
+            .iter()
+            .map(|x| x.code())
+            .collect::<Vec<_>>()
+            .join(":");
+        format!(
+            "tradTpCd={}&order={}&showR0={}",
+            trade_types,
+            self.order.code(),
+            if self.show_completed { "Y" } else { "N" }
+        )
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("{0}")]
@@ -129,10 +512,25 @@ pub enum Error {
     ReqwestError(#[from] reqwest::Error),
     #[error("{0}")]
     SerdeError(#[from] serde_json::error::Error),
-}
-
-fn result_flatten<T, E: From<E1>, E1>(r: Result<Result<T, E>, E1>) -> Result<T, E> {
-    r.map_err(|e| e.into()).and_then(|y| y)
+    #[error("{0}")]
+    IoError(#[from] std::io::Error),
+    #[error("{0}")]
+    CsvError(#[from] csv::Error),
+    #[error("invalid config: {0}")]
+    ConfigError(String),
+    #[error("notify failed: {0}")]
+    NotifyError(String),
+    #[error("server failed: {0}")]
+    ServeError(String),
+    #[error("no dong found under region {0}")]
+    EmptyRegion(String),
+    #[error("{url} (page {page:?}) failed after {attempts} attempts: {reason}")]
+    RetryExhausted {
+        url: String,
+        page: Option<u32>,
+        attempts: u32,
+        reason: String,
+    },
 }
 
 #[async_trait]
@@ -142,23 +540,96 @@ pub trait EstateService: Send + Sync + Sized + Clone + 'static {
     async fn complex_article_list(
         self: Arc<Self>,
         complex_id: String,
+        query: ArticleQuery,
     ) -> Result<Vec<ComplexArticle>, Error>;
 }
 
+pub const BASE_URL: &str = "https://m.land.naver.com";
+
 #[derive(Debug, Clone)]
-pub struct EstateServiceLive;
+pub struct EstateConfig {
+    pub base_url: String,
+    pub requests_per_second: f64,
+    pub timeout: Duration,
+    pub retry: RetryPolicy,
+}
+
+impl Default for EstateConfig {
+    fn default() -> Self {
+        EstateConfig {
+            base_url: BASE_URL.to_string(),
+            requests_per_second: 5.0,
+            timeout: Duration::from_secs(10),
+            retry: RetryPolicy::default(),
+        }
+    }
+}
 
-const BASE_URL: &str = "https://m.land.naver.com";
+#[derive(Debug, Clone)]
+pub struct EstateServiceLive {
+    client: reqwest::Client,
+    config: EstateConfig,
+    limiter: Arc<RateLimiter>,
+}
+
+impl Default for EstateServiceLive {
+    fn default() -> Self {
+        Self::new(EstateConfig::default())
+    }
+}
+
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+impl EstateServiceLive {
+    pub fn new(config: EstateConfig) -> Self {
+        EstateServiceLive {
+            client: reqwest::Client::new(),
+            limiter: Arc::new(RateLimiter::new(config.requests_per_second)),
+            config,
+        }
+    }
+
+    async fn get_text(&self, url: &str, page: Option<u32>) -> Result<String, Error> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            self.limiter.acquire().await;
+            let req = self.client.get(url).timeout(self.config.timeout);
+            let reason = match req.send().await {
+                Ok(resp) if is_retryable_status(resp.status()) => {
+                    format!("HTTP {}", resp.status())
+                }
+                Ok(resp) => match resp.error_for_status()?.text().await {
+                    Ok(text) => return Ok(text),
+                    Err(e) if e.is_timeout() => e.to_string(),
+                    Err(e) => return Err(e.into()),
+                },
+                Err(e) if e.is_timeout() || e.is_connect() => e.to_string(),
+                Err(e) => return Err(e.into()),
+            };
+            if attempt > self.config.retry.max_retries {
+                return Err(Error::RetryExhausted {
+                    url: url.to_string(),
+                    page,
+                    attempts: attempt,
+                    reason,
+                });
+            }
+            time::sleep(self.config.retry.backoff(attempt)).await;
+        }
+    }
+}
 
 #[async_trait]
 impl EstateService for EstateServiceLive {
     async fn region_list(self: Arc<Self>, parent_region_id: String) -> Result<Vec<Region>, Error> {
         let url = format!(
             "{}/map/getRegionList?cortarNo={}",
-            BASE_URL, parent_region_id
+            self.config.base_url, parent_region_id
         );
-        let resp = reqwest::get(&url).await?;
-        let resp_text = resp.text().await?;
+        let resp_text = self.get_text(&url, None).await?;
         let result: RegionListResponse = serde_json::from_str(resp_text.as_str())?;
         Ok(result.result.list)
     }
@@ -166,27 +637,32 @@ impl EstateService for EstateServiceLive {
     async fn complex_list(self: Arc<Self>, region_id: String) -> Result<Vec<Complex>, Error> {
         let url = format!(
             "{}/complex/ajax/complexListByCortarNo?cortarNo={}",
-            BASE_URL, region_id
+            self.config.base_url, region_id
         );
-        let resp = reqwest::get(&url).await?;
-        let result: ComplexListResponse = serde_json::from_str(resp.text().await?.as_str())?;
+        let resp_text = self.get_text(&url, None).await?;
+        let result: ComplexListResponse = serde_json::from_str(resp_text.as_str())?;
         Ok(result.result)
     }
 
     async fn complex_article_list(
         self: Arc<Self>,
         complex_id: String,
+        query: ArticleQuery,
     ) -> Result<Vec<ComplexArticle>, Error> {
         let mut page = 1;
         let mut rs: Vec<ComplexArticle> = Vec::new();
         loop {
-            let url = format!("{}/complex/getComplexArticleList?hscpNo={}&tradTpCd=A1&order=price&showR0=N&page={}", BASE_URL, complex_id, page);
-            let resp = reqwest::get(&url).await?;
-            let resp_text = resp.text().await?;
-            println!("{}", resp_text);
+            let url = format!(
+                "{}/complex/getComplexArticleList?hscpNo={}&{}&page={}",
+                self.config.base_url,
+                complex_id,
+                query.query_string(),
+                page
+            );
+            let resp_text = self.get_text(&url, Some(page)).await?;
             let result: ComplexArticleListResponse = serde_json::from_str(resp_text.as_str())?;
             rs.extend(result.result.list);
-            if !result.result.more_data_yn.eq_ignore_ascii_case("Y") {
+            if !result.result.more_data_yn {
                 break;
             } else {
                 page += 1;
@@ -195,3 +671,89 @@ impl EstateService for EstateServiceLive {
         Ok(rs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn article_query_string() {
+        assert_eq!(
+            ArticleQuery::default().query_string(),
+            "tradTpCd=A1&order=price&showR0=N"
+        );
+        assert_eq!(
+            ArticleQuery::all()
+                .order(ArticleOrder::Date)
+                .show_completed(true)
+                .query_string(),
+            "tradTpCd=A1:B1:B2&order=date&showR0=Y"
+        );
+    }
+
+    #[test]
+    fn parse_price() {
+        let p: Price = "12억 5,000".parse().unwrap();
+        assert_eq!(p, Price::new(BigDecimal::from(125000)));
+        assert_eq!(p.to_string(), "12억 5,000");
+
+        let p: Price = "3억/120".parse().unwrap();
+        assert_eq!(
+            p,
+            Price::with_monthly_rent(BigDecimal::from(30000), BigDecimal::from(120))
+        );
+        assert_eq!(p.to_string(), "3억/120");
+
+        assert_eq!(
+            "9,500".parse::<Price>().unwrap(),
+            Price::new(BigDecimal::from(9500))
+        );
+        assert_eq!(
+            "1.5억".parse::<Price>().unwrap(),
+            Price::new(BigDecimal::from(15000))
+        );
+        assert!("".parse::<Price>().is_err());
+        assert!("협의".parse::<Price>().is_err());
+    }
+
+    #[test]
+    fn article_floor() {
+        let floor = |x: &str| ComplexArticle {
+            flr_info: Some(x.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(floor("12/25").floor(), Some(12));
+        assert_eq!(floor("12/25").total_floors(), Some(25));
+        assert_eq!(floor("B1/15").floor(), Some(-1));
+        assert_eq!(floor("고/25").floor(), None);
+        assert_eq!(floor("고/25").total_floors(), Some(25));
+    }
+
+    #[test]
+    fn article_price_accessors() {
+        let article = ComplexArticle {
+            prc_info: Some("12억".to_string()),
+            same_addr_min_prc: Some("11억 8,000".to_string()),
+            same_addr_max_prc: None,
+            ..Default::default()
+        };
+        assert!(article.same_addr_min_price() < article.price());
+        assert_eq!(article.same_addr_max_price(), None);
+    }
+
+    #[test]
+    fn article_confirmed_date() {
+        let confirmed = |x: &str| {
+            ComplexArticle {
+                cfm_ymd: Some(x.to_string()),
+                ..Default::default()
+            }
+            .confirmed_date()
+        };
+        let date = NaiveDate::from_ymd_opt(2021, 2, 1);
+        assert_eq!(confirmed("21.02.01"), date);
+        assert_eq!(confirmed("21.02.01."), date);
+        assert_eq!(confirmed("2021.02.01"), date);
+        assert_eq!(confirmed(""), None);
+    }
+}
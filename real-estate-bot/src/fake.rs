@@ -0,0 +1,329 @@
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time;
+
+use crate::{
+    estate::{ArticleQuery, Complex, ComplexArticle, Error, EstateService, Region},
+    retry::RetryPolicy,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FakeCall {
+    RegionList(String),
+    ComplexList(String),
+    ComplexArticleList { complex_id: String, page: u32 },
+}
+
+impl FakeCall {
+    pub fn article_page(complex_id: &str, page: u32) -> Self {
+        FakeCall::ComplexArticleList {
+            complex_id: complex_id.to_string(),
+            page,
+        }
+    }
+
+    fn id(&self) -> &str {
+        match self {
+            FakeCall::RegionList(x) | FakeCall::ComplexList(x) => x,
+            FakeCall::ComplexArticleList { complex_id, .. } => complex_id,
+        }
+    }
+
+    fn page(&self) -> Option<u32> {
+        match self {
+            FakeCall::ComplexArticleList { page, .. } => Some(*page),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    regions: HashMap<String, Vec<Region>>,
+    complexes: HashMap<String, Vec<Complex>>,
+    articles: HashMap<String, Vec<ComplexArticle>>,
+    latency: HashMap<String, Duration>,
+    // remaining failures per call, None fails forever
+    failures: HashMap<FakeCall, Option<u32>>,
+    calls: Vec<FakeCall>,
+}
+
+// in-memory `EstateService` seeded up front, for tests that must not touch the network
+#[derive(Debug, Clone)]
+pub struct EstateServiceFake {
+    state: Arc<Mutex<FakeState>>,
+    page_size: usize,
+    retry: RetryPolicy,
+}
+
+impl Default for EstateServiceFake {
+    fn default() -> Self {
+        EstateServiceFake {
+            state: Arc::default(),
+            page_size: 20,
+            retry: RetryPolicy::none(),
+        }
+    }
+}
+
+impl EstateServiceFake {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_regions(self, parent_region_id: &str, regions: Vec<Region>) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .regions
+            .insert(parent_region_id.to_string(), regions);
+        self
+    }
+
+    pub fn with_complexes(self, region_id: &str, complexes: Vec<Complex>) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .complexes
+            .insert(region_id.to_string(), complexes);
+        self
+    }
+
+    pub fn with_articles(self, complex_id: &str, articles: Vec<ComplexArticle>) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .articles
+            .insert(complex_id.to_string(), articles);
+        self
+    }
+
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    // failed calls are retried like `EstateServiceLive` retries a request, none by default
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    // delays every call made with this region or complex id
+    pub fn with_latency(self, id: &str, latency: Duration) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .latency
+            .insert(id.to_string(), latency);
+        self
+    }
+
+    pub fn fail(self, call: FakeCall, times: u32) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .insert(call, Some(times));
+        self
+    }
+
+    pub fn fail_always(self, call: FakeCall) -> Self {
+        self.state.lock().unwrap().failures.insert(call, None);
+        self
+    }
+
+    pub fn calls(&self) -> Vec<FakeCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    // one attempt, recorded in `calls`
+    async fn attempt(&self, call: &FakeCall) -> bool {
+        let (latency, failing) = {
+            let mut state = self.state.lock().unwrap();
+            state.calls.push(call.clone());
+            let latency = state.latency.get(call.id()).copied();
+            let failing = match state.failures.get_mut(call) {
+                Some(None) => true,
+                Some(Some(n)) if *n > 0 => {
+                    *n -= 1;
+                    true
+                }
+                _ => false,
+            };
+            (latency, failing)
+        };
+        if let Some(latency) = latency {
+            time::sleep(latency).await;
+        }
+        !failing
+    }
+
+    async fn enter(&self, call: FakeCall) -> Result<(), Error> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            if self.attempt(&call).await {
+                return Ok(());
+            }
+            if attempt > self.retry.max_retries {
+                return Err(Error::RetryExhausted {
+                    url: format!("fake://{}", call.id()),
+                    page: call.page(),
+                    attempts: attempt,
+                    reason: "scripted failure".to_string(),
+                });
+            }
+            time::sleep(self.retry.backoff(attempt)).await;
+        }
+    }
+}
+
+fn matches_query(article: &ComplexArticle, query: &ArticleQuery) -> bool {
+    let completed = article.trad_cmpl_yn == Some(true);
+    article
+        .trad_tp_cd
+        .as_ref()
+        .is_none_or(|x| query.trade_types.contains(x))
+        && (query.show_completed || !completed)
+}
+
+#[async_trait]
+impl EstateService for EstateServiceFake {
+    async fn region_list(self: Arc<Self>, parent_region_id: String) -> Result<Vec<Region>, Error> {
+        self.enter(FakeCall::RegionList(parent_region_id.clone()))
+            .await?;
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .regions
+            .get(&parent_region_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn complex_list(self: Arc<Self>, region_id: String) -> Result<Vec<Complex>, Error> {
+        self.enter(FakeCall::ComplexList(region_id.clone())).await?;
+        let state = self.state.lock().unwrap();
+        Ok(state.complexes.get(&region_id).cloned().unwrap_or_default())
+    }
+
+    async fn complex_article_list(
+        self: Arc<Self>,
+        complex_id: String,
+        query: ArticleQuery,
+    ) -> Result<Vec<ComplexArticle>, Error> {
+        let articles = {
+            let state = self.state.lock().unwrap();
+            state
+                .articles
+                .get(&complex_id)
+                .cloned()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|x| matches_query(x, &query))
+                .collect::<Vec<_>>()
+        };
+        let pages = articles.len().max(1).div_ceil(self.page_size);
+        for page in 1..=pages {
+            self.enter(FakeCall::article_page(&complex_id, page as u32))
+                .await?;
+        }
+        Ok(articles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::estate::TradeType;
+
+    fn article(atcl_no: &str, trad_tp_cd: &str) -> ComplexArticle {
+        ComplexArticle {
+            atcl_no: Some(atcl_no.to_string()),
+            trad_tp_cd: Some(TradeType::from_code(trad_tp_cd)),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn scripted_page_failure() {
+        let service = Arc::new(
+            EstateServiceFake::new()
+                .page_size(1)
+                .with_articles("8928", vec![article("1", "A1"), article("2", "A1")])
+                .fail(FakeCall::article_page("8928", 2), 1),
+        );
+        let rs = service
+            .clone()
+            .complex_article_list("8928".to_string(), ArticleQuery::default())
+            .await;
+        assert!(matches!(
+            rs,
+            Err(Error::RetryExhausted { page: Some(2), .. })
+        ));
+        let rs = service
+            .clone()
+            .complex_article_list("8928".to_string(), ArticleQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(rs.len(), 2);
+        assert_eq!(service.calls().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn retries_scripted_page_failure() {
+        let retry = RetryPolicy {
+            max_retries: 1,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        };
+        let service = Arc::new(
+            EstateServiceFake::new()
+                .page_size(1)
+                .retry(retry)
+                .with_articles("8928", vec![article("1", "A1"), article("2", "A1")])
+                .fail(FakeCall::article_page("8928", 2), 1)
+                .fail_always(FakeCall::article_page("8929", 1)),
+        );
+        let rs = service
+            .clone()
+            .complex_article_list("8928".to_string(), ArticleQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(rs.len(), 2);
+        assert_eq!(
+            service.calls()[1..],
+            [
+                FakeCall::article_page("8928", 2),
+                FakeCall::article_page("8928", 2)
+            ]
+        );
+        let rs = service
+            .complex_article_list("8929".to_string(), ArticleQuery::default())
+            .await;
+        assert!(matches!(rs, Err(Error::RetryExhausted { attempts: 2, .. })));
+    }
+
+    #[tokio::test]
+    async fn filters_by_trade_type() {
+        let service = Arc::new(
+            EstateServiceFake::new()
+                .with_articles("8928", vec![article("1", "A1"), article("2", "B2")]),
+        );
+        let rs = service
+            .clone()
+            .complex_article_list("8928".to_string(), ArticleQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(rs, vec![article("1", "A1")]);
+        let rs = service
+            .complex_article_list("8928".to_string(), ArticleQuery::all())
+            .await
+            .unwrap();
+        assert_eq!(rs.len(), 2);
+    }
+}
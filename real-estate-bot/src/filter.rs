@@ -0,0 +1,96 @@
+use bigdecimal::BigDecimal;
+
+use crate::estate::{ComplexArticle, TradeType};
+
+// every bound is optional, an article whose value is unknown fails a bound that is set
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListingFilter {
+    pub trade_types: Vec<TradeType>,
+    pub min_area: Option<f64>,
+    pub max_area: Option<f64>,
+    // compare against spc1 (공급면적) instead of spc2 (전용면적)
+    pub supply_area: bool,
+    pub min_price: Option<BigDecimal>,
+    pub max_price: Option<BigDecimal>,
+    pub min_floor: Option<i64>,
+    pub max_floor: Option<i64>,
+}
+
+fn in_range<T: PartialOrd>(value: Option<T>, min: &Option<T>, max: &Option<T>) -> bool {
+    if min.is_none() && max.is_none() {
+        return true;
+    }
+    match value {
+        Some(x) => {
+            min.as_ref().is_none_or(|min| &x >= min) && max.as_ref().is_none_or(|max| &x <= max)
+        }
+        None => false,
+    }
+}
+
+impl ListingFilter {
+    pub fn is_empty(&self) -> bool {
+        *self == ListingFilter::default()
+    }
+
+    pub fn matches(&self, article: &ComplexArticle) -> bool {
+        let area = if self.supply_area {
+            article.supply_area()
+        } else {
+            article.exclusive_area()
+        };
+        (self.trade_types.is_empty()
+            || article
+                .trad_tp_cd
+                .as_ref()
+                .is_some_and(|x| self.trade_types.contains(x)))
+            && in_range(area, &self.min_area, &self.max_area)
+            && in_range(
+                article.price().map(|x| x.amount),
+                &self.min_price,
+                &self.max_price,
+            )
+            && in_range(article.floor(), &self.min_floor, &self.max_floor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn article(spc2: &str, prc_info: &str, flr_info: &str) -> ComplexArticle {
+        ComplexArticle {
+            trad_tp_cd: Some(TradeType::Deal),
+            spc1: Some("112".to_string()),
+            spc2: Some(spc2.to_string()),
+            prc_info: Some(prc_info.to_string()),
+            flr_info: Some(flr_info.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn filter_listings() {
+        let filter = ListingFilter {
+            min_area: Some(59.0),
+            max_price: "12억"
+                .parse::<crate::estate::Price>()
+                .ok()
+                .map(|x| x.amount),
+            min_floor: Some(3),
+            ..Default::default()
+        };
+        assert!(filter.matches(&article("84.98", "11억 5,000", "12/25")));
+        assert!(!filter.matches(&article("49.5", "11억", "12/25")));
+        assert!(!filter.matches(&article("84.98", "12억 1,000", "12/25")));
+        assert!(!filter.matches(&article("84.98", "11억", "2/25")));
+        assert!(!filter.matches(&article("84.98", "11억", "고/25")));
+        assert!(ListingFilter::default().matches(&article("", "", "")));
+
+        let filter = ListingFilter {
+            trade_types: vec![TradeType::Rent],
+            ..Default::default()
+        };
+        assert!(!filter.matches(&article("84.98", "11억", "12/25")));
+    }
+}
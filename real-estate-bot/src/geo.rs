@@ -0,0 +1,227 @@
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+use crate::estate::{Complex, Region};
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct Coord {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+impl Coord {
+    pub fn new(lat: f64, lng: f64) -> Self {
+        Coord { lat, lng }
+    }
+
+    fn parse(lat: &str, lng: &str) -> Option<Self> {
+        let coord = Coord::new(lat.trim().parse().ok()?, lng.trim().parse().ok()?);
+        // the api sends "0" or "" for complexes without a location
+        if coord.lat == 0.0 && coord.lng == 0.0 {
+            None
+        } else {
+            Some(coord)
+        }
+    }
+
+    // great-circle distance by the haversine formula
+    pub fn distance_km(&self, other: &Coord) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lng = (other.lng - self.lng).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+// "37.4847,127.0342"
+impl FromStr for Coord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(',') {
+            Some((lat, lng)) => {
+                Coord::parse(lat, lng).ok_or_else(|| format!("invalid coordinate: {}", s))
+            }
+            None => Err(format!("expected lat,lng: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct BoundingBox {
+    pub south: f64,
+    pub west: f64,
+    pub north: f64,
+    pub east: f64,
+}
+
+impl BoundingBox {
+    pub fn contains(&self, coord: &Coord) -> bool {
+        (self.south..=self.north).contains(&coord.lat)
+            && (self.west..=self.east).contains(&coord.lng)
+    }
+}
+
+// "south,west,north,east"
+impl FromStr for BoundingBox {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let xs = s
+            .split(',')
+            .map(|x| x.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("invalid bounding box: {}: {}", s, e))?;
+        match xs[..] {
+            [south, west, north, east] if south <= north && west <= east => Ok(BoundingBox {
+                south,
+                west,
+                north,
+                east,
+            }),
+            _ => Err(format!("expected south,west,north,east: {}", s)),
+        }
+    }
+}
+
+impl Complex {
+    pub fn coord(&self) -> Option<Coord> {
+        Coord::parse(&self.lat, &self.lng)
+    }
+}
+
+// MapXCrdn is the longitude and MapYCrdn the latitude
+impl Region {
+    pub fn coord(&self) -> Option<Coord> {
+        Coord::parse(&self.map_ycrdn, &self.map_xcrdn)
+    }
+}
+
+// complexes within `radius_km` of `center`, nearest first
+pub fn within_radius(complexes: &[Complex], center: &Coord, radius_km: f64) -> Vec<(Complex, f64)> {
+    let mut rs = complexes
+        .iter()
+        .filter_map(|x| {
+            let d = x.coord()?.distance_km(center);
+            if d <= radius_km {
+                Some((x.clone(), d))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    rs.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    rs
+}
+
+pub fn within_box(complexes: &[Complex], bbox: &BoundingBox) -> Vec<Complex> {
+    complexes
+        .iter()
+        .filter(|x| x.coord().is_some_and(|c| bbox.contains(&c)))
+        .cloned()
+        .collect()
+}
+
+// a FeatureCollection of points, complexes without a location are left out
+pub fn to_geojson(complexes: &[Complex]) -> Value {
+    let features = complexes
+        .iter()
+        .filter_map(|x| {
+            let coord = x.coord()?;
+            Some(json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [coord.lng, coord.lat],
+                },
+                "properties": {
+                    "hscpNo": x.hscp_no,
+                    "name": x.hscp_nm,
+                    "type": x.hscp_type_nm,
+                    "cortarNo": x.cortar_no,
+                    "dealCnt": x.deal_cnt,
+                    "leaseCnt": x.lease_cnt,
+                    "rentCnt": x.rent_cnt,
+                    "totalCnt": x.deal_cnt + x.lease_cnt + x.rent_cnt,
+                },
+            }))
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complex(hscp_no: &str, lat: &str, lng: &str) -> Complex {
+        Complex {
+            hscp_no: hscp_no.to_string(),
+            lat: lat.to_string(),
+            lng: lng.to_string(),
+            deal_cnt: 3,
+            lease_cnt: 2,
+            ..Default::default()
+        }
+    }
+
+    fn complexes() -> Vec<Complex> {
+        vec![
+            // 개포동 / 도곡동 / 잠실
+            complex("1", "37.483246", "127.055786"),
+            complex("2", "37.490870", "127.046850"),
+            complex("3", "37.511950", "127.086080"),
+            complex("4", "", ""),
+        ]
+    }
+
+    #[test]
+    fn parse_coordinates() {
+        assert_eq!(complexes()[3].coord(), None);
+        assert_eq!(
+            "37.5, 127.0".parse::<Coord>().unwrap(),
+            Coord::new(37.5, 127.0)
+        );
+        assert!("37.5".parse::<Coord>().is_err());
+        assert!("37.6,127.1,37.5,127.2".parse::<BoundingBox>().is_err());
+        let region = Region {
+            map_xcrdn: "127.0342".to_string(),
+            map_ycrdn: "37.4847".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(region.coord(), Some(Coord::new(37.4847, 127.0342)));
+    }
+
+    #[test]
+    fn radius_and_box() {
+        // 도곡역
+        let center = Coord::new(37.490922, 127.055452);
+        let d = center.distance_km(&complexes()[0].coord().unwrap());
+        assert!((0.8..0.9).contains(&d), "{}", d);
+        let rs = within_radius(&complexes(), &center, 1.5);
+        assert_eq!(
+            rs.iter().map(|x| x.0.hscp_no.as_str()).collect::<Vec<_>>(),
+            vec!["2", "1"]
+        );
+        let bbox = "37.50,127.0,37.52,127.1".parse::<BoundingBox>().unwrap();
+        assert_eq!(within_box(&complexes(), &bbox)[0].hscp_no, "3");
+    }
+
+    #[test]
+    fn geojson() {
+        let rs = to_geojson(&complexes());
+        assert_eq!(rs["features"].as_array().unwrap().len(), 3);
+        let feature = &rs["features"][0];
+        assert_eq!(
+            feature["geometry"]["coordinates"],
+            json!([127.055786, 37.483246])
+        );
+        assert_eq!(feature["properties"]["totalCnt"], json!(5));
+    }
+}
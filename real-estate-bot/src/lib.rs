@@ -0,0 +1,19 @@
+pub mod cache;
+pub mod code;
+pub mod crawl;
+pub mod daemon;
+pub mod estate;
+pub mod fake;
+pub mod filter;
+pub mod geo;
+pub mod notify;
+pub mod output;
+pub mod property;
+pub mod region;
+pub mod report;
+pub mod retry;
+pub mod serve;
+pub mod snapshot;
+pub mod stats;
+pub mod trend;
+pub mod watch;
@@ -1,43 +1,392 @@
-use futures::TryFutureExt;
-use futures::{
-    stream::{self, StreamExt},
-    FutureExt,
+use clap::{Args, Parser, Subcommand};
+use std::{io, net::SocketAddr, sync::Arc};
+use tokio::signal;
+
+use real_estate_bot::cache::{CacheConfig, CacheStorage, CachedEstateService};
+use real_estate_bot::crawl::{crawl_complexes, crawl_region};
+use real_estate_bot::daemon::{Daemon, DaemonConfig};
+use real_estate_bot::estate::{
+    self, ArticleQuery, EstateConfig, EstateService, EstateServiceLive, Price, TradeType,
 };
+use real_estate_bot::filter::ListingFilter;
+use real_estate_bot::geo::{to_geojson, within_box, within_radius, BoundingBox, Coord};
+use real_estate_bot::notify::{notify_diff, Notifier, TelegramNotifier};
+use real_estate_bot::output::{write_output, OutputFormat};
+use real_estate_bot::property;
+use real_estate_bot::region::crawl_region_tree;
+use real_estate_bot::report::write_report;
+use real_estate_bot::serve::serve;
+use real_estate_bot::snapshot::{Snapshot, SnapshotStore};
+use real_estate_bot::stats::{summarize, SummaryLevel};
+use real_estate_bot::trend::{article_history, complex_trend, TrendQuery};
+use real_estate_bot::watch::{crawl_watchlist, Watchlist};
 
-use estate::EstateServiceLive;
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    /// table, json or csv
+    #[clap(short, long, global = true, default_value = "table")]
+    format: OutputFormat,
+    /// upstream requests per second
+    #[clap(long, global = true, default_value_t = 5.0)]
+    rps: f64,
+    /// number of complexes / regions fetched at once
+    #[clap(long, global = true, default_value_t = 8)]
+    concurrency: usize,
+    /// where region and complex lists are cached between runs
+    #[clap(long, global = true, default_value = ".cache/real-estate-bot")]
+    cache_dir: String,
+    /// always fetch from upstream
+    #[clap(long, global = true)]
+    no_cache: bool,
+    #[clap(subcommand)]
+    command: Command,
+}
 
-use crate::estate::{Complex, ComplexArticle, Error, EstateService};
-use std::{convert::identity, rc::Rc};
-use std::sync::Arc;
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// child regions of a cortarNo
+    Regions {
+        cortar_no: String,
+        /// walk down to the dong level
+        #[clap(short, long)]
+        recursive: bool,
+    },
+    /// complexes in a dong level cortarNo
+    Complexes { cortar_no: String },
+    /// articles listed under a complex
+    Articles {
+        hscp_no: String,
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    /// articles of every complex in a region
+    Crawl {
+        cortar_no: String,
+        /// crawl every dong under cortar_no instead of treating it as a dong
+        #[clap(short, long)]
+        recursive: bool,
+        /// append the result to this snapshot file and report changes since the last run
+        #[clap(long)]
+        snapshot: Option<String>,
+        /// print one row per unit instead of one per ad
+        #[clap(long)]
+        consolidate: bool,
+        /// write an html report into this directory
+        #[clap(long)]
+        report: Option<String>,
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    /// complexes of a region within a radius or a bounding box
+    Near {
+        cortar_no: String,
+        #[clap(short, long)]
+        recursive: bool,
+        /// "lat,lng", used with --radius
+        #[clap(long, requires = "radius", conflicts_with = "bbox")]
+        center: Option<Coord>,
+        /// in km
+        #[clap(long, requires = "center")]
+        radius: Option<f64>,
+        /// "south,west,north,east"
+        #[clap(long)]
+        bbox: Option<BoundingBox>,
+        /// print a GeoJSON FeatureCollection instead of --format
+        #[clap(long)]
+        geojson: bool,
+    },
+    /// price statistics of a region, rolled up per complex, dong or gu
+    Stats {
+        cortar_no: String,
+        #[clap(short, long)]
+        recursive: bool,
+        /// complex, dong or gu
+        #[clap(short, long, default_value = "complex")]
+        level: SummaryLevel,
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    /// price history from a snapshot file, per complex or with --articles per article
+    History {
+        snapshot: String,
+        /// only this hscpNo
+        #[clap(long)]
+        complex: Option<String>,
+        /// only the snapshots of the last N days
+        #[clap(long)]
+        days: Option<i64>,
+        /// deal, lease or rent (A1, B1, B2), can be repeated
+        #[clap(short, long = "trade-type")]
+        trade_types: Vec<TradeType>,
+        #[clap(long)]
+        articles: bool,
+    },
+    /// serve the stored snapshots as a paginated json api until SIGTERM
+    Serve {
+        #[clap(long, default_value = "snapshots.jsonl")]
+        snapshot: String,
+        #[clap(long, default_value = "127.0.0.1:8080")]
+        addr: SocketAddr,
+    },
+    /// re-crawl the regions in a yaml config on their schedules until SIGTERM
+    Daemon { config: String },
+    /// crawl what a yaml watchlist refers to and print the listings matching its rules
+    Watch { watchlist: String },
+}
 
-mod estate;
+#[derive(Args, Debug)]
+struct FilterArgs {
+    /// deal, lease or rent (A1, B1, B2), can be repeated
+    #[clap(short, long = "trade-type")]
+    trade_types: Vec<TradeType>,
+    /// minimum area in ㎡
+    #[clap(long)]
+    min_area: Option<f64>,
+    /// maximum area in ㎡
+    #[clap(long)]
+    max_area: Option<f64>,
+    /// compare areas against spc1 (supply) instead of spc2 (exclusive)
+    #[clap(long)]
+    supply_area: bool,
+    /// e.g. "9억" or "9억 5,000"
+    #[clap(long)]
+    min_price: Option<Price>,
+    #[clap(long)]
+    max_price: Option<Price>,
+    #[clap(long)]
+    min_floor: Option<i64>,
+    #[clap(long)]
+    max_floor: Option<i64>,
+}
 
-const GANG_NAM_GU: &'static str = "1168000000";
-const SONG_PA_GU: &'static str = "1171000000";
-const SU_JUNG_GU: &'static str = "4113100000";
+impl FilterArgs {
+    fn query(&self) -> ArticleQuery {
+        if self.trade_types.is_empty() {
+            ArticleQuery::all()
+        } else {
+            ArticleQuery::new().trade_types(self.trade_types.clone())
+        }
+    }
 
-const CHANG_GOK_DONG: &'static str = "4113110800";
+    fn filter(&self) -> ListingFilter {
+        ListingFilter {
+            trade_types: self.trade_types.clone(),
+            min_area: self.min_area,
+            max_area: self.max_area,
+            supply_area: self.supply_area,
+            min_price: self.min_price.clone().map(|x| x.amount),
+            max_price: self.max_price.clone().map(|x| x.amount),
+            min_floor: self.min_floor,
+            max_floor: self.max_floor,
+        }
+    }
+}
 
 #[tokio::main]
-async fn main() {
-}
+async fn main() -> Result<(), estate::Error> {
+    let cli = Cli::parse();
+    let live = Arc::new(EstateServiceLive::new(EstateConfig {
+        requests_per_second: cli.rps,
+        ..EstateConfig::default()
+    }));
+    let cache = if cli.no_cache {
+        CacheConfig::disabled()
+    } else {
+        CacheConfig::default()
+    };
+    let service = Arc::new(CachedEstateService::new(
+        live,
+        cache.storage(CacheStorage::Disk(cli.cache_dir.clone().into())),
+    ));
+    let out = io::stdout();
+    match cli.command {
+        Command::Regions {
+            cortar_no,
+            recursive,
+        } => {
+            let xs = if recursive {
+                let tree = crawl_region_tree(service, cortar_no, cli.concurrency).await?;
+                tree.nodes.into_values().map(|x| x.region).collect()
+            } else {
+                service.region_list(cortar_no).await?
+            };
+            write_output(&xs, cli.format, out.lock())
+        }
+        Command::Complexes { cortar_no } => {
+            let xs = service.complex_list(cortar_no).await?;
+            write_output(&xs, cli.format, out.lock())
+        }
+        Command::Articles { hscp_no, filter } => {
+            let xs = service
+                .complex_article_list(hscp_no, filter.query())
+                .await?;
+            let filter = filter.filter();
+            let xs = xs
+                .into_iter()
+                .filter(|x| filter.matches(x))
+                .collect::<Vec<_>>();
+            write_output(&xs, cli.format, out.lock())
+        }
+        Command::Crawl {
+            cortar_no,
+            recursive,
+            snapshot,
+            consolidate,
+            report: report_dir,
+            filter,
+        } => {
+            let report = crawl_region(
+                service,
+                cortar_no.clone(),
+                recursive,
+                filter.query(),
+                cli.concurrency,
+            )
+            .await?;
+            for failure in report.failures.iter() {
+                eprintln!("{}: {}", failure.complex_id, failure.error);
+            }
+            for failure in report.region_failures.iter() {
+                eprintln!("region {}: {}", failure.region_no, failure.error);
+            }
+            let listing_filter = filter.filter();
+            let xs = report
+                .entries
+                .iter()
+                .filter(|x| listing_filter.matches(&x.article))
+                .cloned()
+                .collect::<Vec<_>>();
+            if consolidate {
+                write_output(&property::consolidate(&xs), cli.format, out.lock())?;
+            } else {
+                write_output(&xs, cli.format, out.lock())?;
+            }
 
-fn result_flatten<T, E: From<E1>, E1>(r: Result<Result<T, E>, E1>) -> Result<T, E> {
-    r.map_err(|e| e.into()).and_then(|y| y)
+            let mut diff = None;
+            if let Some(path) = snapshot {
+                let store = SnapshotStore::new(path);
+                diff = store.record(&Snapshot::from_report(cortar_no.clone(), report))?;
+                if let Some(diff) = &diff {
+                    eprintln!(
+                        "{} new, {} removed, {} price changed",
+                        diff.added.len(),
+                        diff.removed.len(),
+                        diff.price_changed.len()
+                    );
+                    if let Some(notifier) = TelegramNotifier::from_env() {
+                        notify_diff(&notifier, diff).await?;
+                    }
+                }
+            }
+            if let Some(dir) = report_dir {
+                let path = write_report(dir, &cortar_no, &xs, diff.as_ref())?;
+                eprintln!("report written to {}", path.display());
+            }
+            Ok(())
+        }
+        Command::Near {
+            cortar_no,
+            recursive,
+            center,
+            radius,
+            bbox,
+            geojson,
+        } => {
+            let xs = crawl_complexes(service, cortar_no, recursive, cli.concurrency).await?;
+            let xs = match (center, radius, bbox) {
+                (Some(center), Some(radius), _) => within_radius(&xs, &center, radius)
+                    .into_iter()
+                    .map(|x| x.0)
+                    .collect(),
+                (_, _, Some(bbox)) => within_box(&xs, &bbox),
+                _ => xs,
+            };
+            if geojson {
+                serde_json::to_writer_pretty(out.lock(), &to_geojson(&xs))?;
+                println!();
+                Ok(())
+            } else {
+                write_output(&xs, cli.format, out.lock())
+            }
+        }
+        Command::Stats {
+            cortar_no,
+            recursive,
+            level,
+            filter,
+        } => {
+            let report = crawl_region(
+                service,
+                cortar_no,
+                recursive,
+                filter.query(),
+                cli.concurrency,
+            )
+            .await?;
+            let listing_filter = filter.filter();
+            let xs = report
+                .entries
+                .into_iter()
+                .filter(|x| listing_filter.matches(&x.article))
+                .collect::<Vec<_>>();
+            let rows = summarize(&xs, level)
+                .iter()
+                .flat_map(|x| x.rows())
+                .collect::<Vec<_>>();
+            write_output(&rows, cli.format, out.lock())
+        }
+        Command::History {
+            snapshot,
+            complex,
+            days,
+            trade_types,
+            articles,
+        } => {
+            let snapshots = SnapshotStore::new(snapshot).load_all()?;
+            let mut query = TrendQuery::new().trade_types(trade_types);
+            if let Some(x) = complex {
+                query = query.complex_id(&x);
+            }
+            if let Some(x) = days {
+                query = query.last_days(x);
+            }
+            if articles {
+                write_output(&article_history(&snapshots, &query), cli.format, out.lock())
+            } else {
+                write_output(&complex_trend(&snapshots, &query), cli.format, out.lock())
+            }
+        }
+        Command::Watch { watchlist } => {
+            let watchlist = Watchlist::load(watchlist)?;
+            let report = crawl_watchlist(service, &watchlist, cli.concurrency).await?;
+            for failure in report.failures.iter() {
+                eprintln!("{}: {}", failure.complex_id, failure.error);
+            }
+            for failure in report.region_failures.iter() {
+                eprintln!("region {}: {}", failure.region_no, failure.error);
+            }
+            let xs = watchlist.evaluate(&report.entries)?;
+            write_output(&xs, cli.format, out.lock())
+        }
+        Command::Serve { snapshot, addr } => {
+            serve(addr, SnapshotStore::new(snapshot), shutdown_signal()).await
+        }
+        Command::Daemon { config } => {
+            let config = DaemonConfig::load(config)?;
+            let notifier = TelegramNotifier::from_env().map(|x| Arc::new(x) as Arc<dyn Notifier>);
+            Daemon::new(service, notifier, config)
+                .run(shutdown_signal())
+                .await
+        }
+    }
 }
 
-async fn get_result_list<T: EstateService>(
-    service: Arc<T>,
-    region_no: String,
-) -> Result<Vec<ComplexArticle>, estate::Error> {
-    let xs = service.clone().complex_list(region_no).await?;
-    stream::iter(xs)
-        .map(|x| service.clone().complex_article_list(x.cortar_no))
-        .then(tokio::spawn)
-        .collect::<Vec<_>>()
-        .await
-        .into_iter()
-        .map(result_flatten)
-        .collect::<Result<Vec<_>, _>>()
-        .map(|xs| xs.into_iter().flatten().collect::<Vec<_>>())
+async fn shutdown_signal() {
+    let mut terminate =
+        signal::unix::signal(signal::unix::SignalKind::terminate()).expect("SIGTERM handler");
+    tokio::select! {
+        _ = terminate.recv() => {}
+        _ = signal::ctrl_c() => {}
+    }
 }
@@ -0,0 +1,243 @@
+use async_trait::async_trait;
+use std::{
+    env,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    estate::{Error, ListingWithComplex},
+    property::{consolidate, Property},
+    snapshot::{PriceChange, SnapshotDiff},
+};
+
+pub(crate) const ARTICLE_URL: &str = "https://m.land.naver.com/article/info";
+const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+
+pub const TELEGRAM_TOKEN_ENV: &str = "TELEGRAM_TOKEN";
+pub const TELEGRAM_CHAT_ID_ENV: &str = "TELEGRAM_CHAT_ID";
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, message: String) -> Result<(), Error>;
+}
+
+#[derive(Debug, Clone)]
+pub struct TelegramNotifier {
+    client: reqwest::Client,
+    token: String,
+    chat_id: String,
+}
+
+impl TelegramNotifier {
+    pub fn new(token: String, chat_id: String) -> Self {
+        TelegramNotifier {
+            client: reqwest::Client::new(),
+            token,
+            chat_id,
+        }
+    }
+
+    // None unless both TELEGRAM_TOKEN and TELEGRAM_CHAT_ID are set
+    pub fn from_env() -> Option<Self> {
+        let token = env::var(TELEGRAM_TOKEN_ENV).ok()?;
+        let chat_id = env::var(TELEGRAM_CHAT_ID_ENV).ok()?;
+        Some(Self::new(token, chat_id))
+    }
+}
+
+#[derive(Debug, serde_derive::Deserialize)]
+struct TelegramResponse {
+    ok: bool,
+    description: Option<String>,
+}
+
+#[async_trait]
+impl Notifier for TelegramNotifier {
+    async fn notify(&self, message: String) -> Result<(), Error> {
+        let url = format!("{}/bot{}/sendMessage", TELEGRAM_API_URL, self.token);
+        let resp: TelegramResponse = self
+            .client
+            .post(&url)
+            .json(&serde_json::json!({
+                "chat_id": self.chat_id,
+                "text": message,
+                "disable_web_page_preview": true,
+            }))
+            .send()
+            .await?
+            .json()
+            .await?;
+        if resp.ok {
+            Ok(())
+        } else {
+            Err(Error::NotifyError(resp.description.unwrap_or_default()))
+        }
+    }
+}
+
+// keeps every message in memory instead of sending it
+#[derive(Debug, Clone, Default)]
+pub struct RecordingNotifier {
+    messages: Arc<Mutex<Vec<String>>>,
+}
+
+impl RecordingNotifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Notifier for RecordingNotifier {
+    async fn notify(&self, message: String) -> Result<(), Error> {
+        self.messages.lock().unwrap().push(message);
+        Ok(())
+    }
+}
+
+fn or_dash(s: &Option<String>) -> &str {
+    s.as_deref().unwrap_or("-")
+}
+
+fn format_article(listing: &ListingWithComplex) -> String {
+    let article = &listing.article;
+    let complex_name = if listing.complex.hscp_nm.is_empty() {
+        or_dash(&article.atcl_nm)
+    } else {
+        listing.complex.hscp_nm.as_str()
+    };
+    format!(
+        "{} {}\n{} / {}㎡ ({}㎡)\n{}/{}",
+        complex_name,
+        or_dash(&article.bild_nm),
+        or_dash(&article.flr_info),
+        or_dash(&article.spc2),
+        or_dash(&article.spc1),
+        ARTICLE_URL,
+        or_dash(&article.atcl_no)
+    )
+}
+
+pub fn format_new_listing(entry: &ListingWithComplex) -> String {
+    format!(
+        "[신규] {} {}\n{}",
+        or_dash(&entry.article.trad_tp_nm),
+        or_dash(&entry.article.prc_info),
+        format_article(entry)
+    )
+}
+
+// a unit advertised by several agencies is announced once
+pub fn format_new_property(property: &Property) -> String {
+    let message = format_new_listing(&property.listing());
+    match property.agencies.len() {
+        0 | 1 => message,
+        n => {
+            let (head, tail) = message.split_once('\n').unwrap_or((&message, ""));
+            format!("{} (중개사 {}곳)\n{}", head, n, tail)
+        }
+    }
+}
+
+pub fn format_price_change(change: &PriceChange) -> String {
+    let show = |x: &Option<_>| match x {
+        Some(price) => format!("{}", price),
+        None => "-".to_string(),
+    };
+    format!(
+        "[가격변동] {} {} -> {}\n{}",
+        or_dash(&change.entry.article.trad_tp_nm),
+        show(&change.old_price),
+        show(&change.new_price),
+        format_article(&change.entry)
+    )
+}
+
+pub fn format_diff(diff: &SnapshotDiff) -> Vec<String> {
+    consolidate(&diff.added)
+        .iter()
+        .map(format_new_property)
+        .chain(diff.price_changed.iter().map(format_price_change))
+        .collect()
+}
+
+pub async fn notify_diff<N: Notifier + ?Sized>(
+    notifier: &N,
+    diff: &SnapshotDiff,
+) -> Result<usize, Error> {
+    let messages = format_diff(diff);
+    let n = messages.len();
+    for message in messages {
+        notifier.notify(message).await?;
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::estate::{Complex, ComplexArticle};
+
+    fn listing(atcl_no: &str, prc_info: &str) -> ListingWithComplex {
+        let complex = Complex {
+            hscp_no: "8928".to_string(),
+            hscp_nm: "위례자이".to_string(),
+            ..Default::default()
+        };
+        let article = ComplexArticle {
+            atcl_no: Some(atcl_no.to_string()),
+            atcl_nm: Some("위례".to_string()),
+            bild_nm: Some("101동".to_string()),
+            trad_tp_nm: Some("매매".to_string()),
+            flr_info: Some("12/25".to_string()),
+            spc1: Some("112".to_string()),
+            spc2: Some("84.98".to_string()),
+            prc_info: Some(prc_info.to_string()),
+            ..Default::default()
+        };
+        ListingWithComplex::new(complex, article)
+    }
+
+    #[tokio::test]
+    async fn notify_new_and_changed_listings() {
+        let diff = SnapshotDiff {
+            added: vec![listing("2100000001", "12억 5,000")],
+            removed: vec![listing("2100000002", "11억")],
+            price_changed: vec![PriceChange {
+                entry: listing("2100000003", "9억"),
+                old_price: "9억 5,000".parse().ok(),
+                new_price: "9억".parse().ok(),
+            }],
+        };
+        let notifier = RecordingNotifier::new();
+        assert_eq!(notify_diff(&notifier, &diff).await.unwrap(), 2);
+        assert_eq!(
+            notifier.messages(),
+            vec![
+                "[신규] 매매 12억 5,000\n위례자이 101동\n12/25 / 84.98㎡ (112㎡)\nhttps://m.land.naver.com/article/info/2100000001",
+                "[가격변동] 매매 9억 5,000 -> 9억\n위례자이 101동\n12/25 / 84.98㎡ (112㎡)\nhttps://m.land.naver.com/article/info/2100000003",
+            ]
+        );
+    }
+
+    #[test]
+    fn announce_unit_once() {
+        let ad = |atcl_no: &str, rltr_nm: &str| {
+            let mut x = listing(atcl_no, "12억");
+            x.article.same_addr_hash = Some("a".to_string());
+            x.article.rltr_nm = Some(rltr_nm.to_string());
+            x
+        };
+        let diff = SnapshotDiff {
+            added: vec![ad("1", "행복공인"), ad("2", "위례공인")],
+            ..Default::default()
+        };
+        let messages = format_diff(&diff);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with("[신규] 매매 12억 (중개사 2곳)\n위례자이"));
+    }
+}
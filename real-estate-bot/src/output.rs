@@ -0,0 +1,195 @@
+use serde::Serialize;
+use std::{io::Write, str::FromStr};
+use unicode_width::UnicodeWidthStr;
+
+use crate::estate::{Complex, ComplexArticle, Error, ListingWithComplex, Region};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!("unknown output format: {}", s)),
+        }
+    }
+}
+
+pub trait Tabular {
+    fn headers() -> Vec<&'static str>;
+    fn row(&self) -> Vec<String>;
+}
+
+fn or_empty(s: &Option<String>) -> String {
+    s.clone().unwrap_or_default()
+}
+
+impl Tabular for Region {
+    fn headers() -> Vec<&'static str> {
+        vec!["cortarNo", "name", "type"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.cortar_no.clone(),
+            self.cortar_nm.clone(),
+            self.cortar_type.to_string(),
+        ]
+    }
+}
+
+impl Tabular for Complex {
+    fn headers() -> Vec<&'static str> {
+        vec!["hscpNo", "name", "type", "deal", "lease", "rent"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.hscp_no.clone(),
+            self.hscp_nm.clone(),
+            self.hscp_type_nm.clone(),
+            self.deal_cnt.to_string(),
+            self.lease_cnt.to_string(),
+            self.rent_cnt.to_string(),
+        ]
+    }
+}
+
+impl Tabular for ComplexArticle {
+    fn headers() -> Vec<&'static str> {
+        vec![
+            "atclNo",
+            "trade",
+            "price",
+            "spc1",
+            "spc2",
+            "floor",
+            "building",
+            "confirmed",
+        ]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            or_empty(&self.atcl_no),
+            or_empty(&self.trad_tp_nm),
+            or_empty(&self.prc_info),
+            or_empty(&self.spc1),
+            or_empty(&self.spc2),
+            or_empty(&self.flr_info),
+            or_empty(&self.bild_nm),
+            or_empty(&self.cfm_ymd),
+        ]
+    }
+}
+
+impl Tabular for ListingWithComplex {
+    fn headers() -> Vec<&'static str> {
+        let mut rs = vec!["hscpNo", "complex"];
+        rs.extend(ComplexArticle::headers());
+        rs
+    }
+
+    fn row(&self) -> Vec<String> {
+        let mut rs = vec![self.complex.hscp_no.clone(), self.complex.hscp_nm.clone()];
+        rs.extend(self.article.row());
+        rs
+    }
+}
+
+pub fn render_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|x| x.width()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.width());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(x, w)| format!("{}{}", x, " ".repeat(w - x.width())))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    let separator = widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>();
+    let mut rs = vec![
+        line(headers.to_vec()),
+        line(separator.iter().map(|x| x.as_str()).collect()),
+    ];
+    rs.extend(
+        rows.iter()
+            .map(|row| line(row.iter().map(|x| x.as_str()).collect())),
+    );
+    rs.join("\n") + "\n"
+}
+
+pub fn write_output<T: Tabular + Serialize, W: Write>(
+    xs: &[T],
+    format: OutputFormat,
+    mut out: W,
+) -> Result<(), Error> {
+    match format {
+        OutputFormat::Table => {
+            let rows = xs.iter().map(|x| x.row()).collect::<Vec<_>>();
+            out.write_all(render_table(&T::headers(), &rows).as_bytes())?;
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, xs)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            writer.write_record(T::headers())?;
+            for x in xs {
+                writer.write_record(x.row())?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::estate::CortarType;
+
+    fn regions() -> Vec<Region> {
+        vec![Region {
+            cortar_no: "1168010300".to_string(),
+            cortar_nm: "개포동".to_string(),
+            cortar_type: CortarType::Section,
+            ..Default::default()
+        }]
+    }
+
+    fn render(format: OutputFormat) -> String {
+        let mut out = Vec::new();
+        write_output(&regions(), format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn table_and_csv() {
+        assert_eq!(
+            render(OutputFormat::Table),
+            "cortarNo    name    type\n----------  ------  ----\n1168010300  개포동  sec\n"
+        );
+        assert_eq!(
+            render(OutputFormat::Csv),
+            "cortarNo,name,type\n1168010300,개포동,sec\n"
+        );
+    }
+}
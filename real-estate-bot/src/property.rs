@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    estate::{Complex, ComplexArticle, ListingWithComplex, Price, TradeType},
+    output::Tabular,
+};
+
+// the same unit advertised by several agencies shares sameAddrHash within a complex and trade type
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+pub struct PropertyKey {
+    pub hscp_no: String,
+    pub trad_tp_cd: Option<TradeType>,
+    // sameAddrHash, or atclNo for an article without one
+    pub unit: String,
+}
+
+impl PropertyKey {
+    pub fn of(listing: &ListingWithComplex) -> Option<PropertyKey> {
+        let article = &listing.article;
+        let unit = article
+            .same_addr_hash
+            .as_ref()
+            .filter(|x| !x.is_empty())
+            .or(article.atcl_no.as_ref())?;
+        Some(PropertyKey {
+            hscp_no: listing.complex.hscp_no.clone(),
+            trad_tp_cd: article.trad_tp_cd.clone(),
+            unit: unit.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde_derive::Serialize)]
+pub struct Agency {
+    pub rltr_nm: Option<String>,
+    pub cp_nm: Option<String>,
+}
+
+// one real unit and every ad listing it
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize)]
+pub struct Property {
+    pub key: Option<PropertyKey>,
+    pub complex: Complex,
+    pub articles: Vec<ComplexArticle>,
+    pub agencies: Vec<Agency>,
+    pub min_price: Option<Price>,
+    pub max_price: Option<Price>,
+}
+
+impl Property {
+    fn new(key: Option<PropertyKey>, complex: Complex) -> Self {
+        Property {
+            key,
+            complex,
+            articles: Vec::new(),
+            agencies: Vec::new(),
+            min_price: None,
+            max_price: None,
+        }
+    }
+
+    fn push(&mut self, article: &ComplexArticle) {
+        let agency = Agency {
+            rltr_nm: article.rltr_nm.clone(),
+            cp_nm: article.cp_nm.clone(),
+        };
+        if !self.agencies.contains(&agency) {
+            self.agencies.push(agency);
+        }
+        if let Some(price) = article.price() {
+            if self.min_price.as_ref().is_none_or(|x| price < *x) {
+                self.min_price = Some(price.clone());
+            }
+            if self.max_price.as_ref().is_none_or(|x| price > *x) {
+                self.max_price = Some(price);
+            }
+        }
+        self.articles.push(article.clone());
+    }
+
+    // the first ad seen for the unit
+    pub fn article(&self) -> &ComplexArticle {
+        &self.articles[0]
+    }
+
+    pub fn listing(&self) -> ListingWithComplex {
+        ListingWithComplex::new(self.complex.clone(), self.article().clone())
+    }
+}
+
+// groups listings into properties in the order they are first seen
+pub fn consolidate(listings: &[ListingWithComplex]) -> Vec<Property> {
+    let mut index: BTreeMap<PropertyKey, usize> = BTreeMap::new();
+    let mut rs: Vec<Property> = Vec::new();
+    for listing in listings {
+        let key = PropertyKey::of(listing);
+        let i = match key.as_ref().and_then(|x| index.get(x)) {
+            Some(i) => *i,
+            None => {
+                if let Some(key) = key.clone() {
+                    index.insert(key, rs.len());
+                }
+                rs.push(Property::new(key, listing.complex.clone()));
+                rs.len() - 1
+            }
+        };
+        rs[i].push(&listing.article);
+    }
+    rs
+}
+
+impl Tabular for Property {
+    fn headers() -> Vec<&'static str> {
+        vec![
+            "hscpNo", "complex", "trade", "building", "floor", "spc2", "ads", "agencies", "min",
+            "max",
+        ]
+    }
+
+    fn row(&self) -> Vec<String> {
+        let article = self.article();
+        let show = |x: &Option<Price>| x.as_ref().map(|x| x.to_string()).unwrap_or_default();
+        vec![
+            self.complex.hscp_no.clone(),
+            self.complex.hscp_nm.clone(),
+            article.trad_tp_nm.clone().unwrap_or_default(),
+            article.bild_nm.clone().unwrap_or_default(),
+            article.flr_info.clone().unwrap_or_default(),
+            article.spc2.clone().unwrap_or_default(),
+            self.articles.len().to_string(),
+            self.agencies
+                .iter()
+                .filter_map(|x| x.rltr_nm.clone().or_else(|| x.cp_nm.clone()))
+                .collect::<Vec<_>>()
+                .join(", "),
+            show(&self.min_price),
+            show(&self.max_price),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(
+        atcl_no: &str,
+        hash: Option<&str>,
+        rltr_nm: &str,
+        prc_info: &str,
+    ) -> ListingWithComplex {
+        ListingWithComplex::new(
+            Complex {
+                hscp_no: "8928".to_string(),
+                ..Default::default()
+            },
+            ComplexArticle {
+                atcl_no: Some(atcl_no.to_string()),
+                trad_tp_cd: Some(TradeType::Deal),
+                same_addr_hash: hash.map(|x| x.to_string()),
+                rltr_nm: Some(rltr_nm.to_string()),
+                cp_nm: Some("부동산뱅크".to_string()),
+                prc_info: Some(prc_info.to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn consolidate_by_same_addr_hash() {
+        let rs = consolidate(&[
+            listing("1", Some("a"), "행복공인", "12억"),
+            listing("2", Some("b"), "행복공인", "10억"),
+            listing("3", Some("a"), "위례공인", "11억 5,000"),
+            listing("4", Some("a"), "행복공인", "12억"),
+            listing("5", None, "위례공인", "9억"),
+        ]);
+        assert_eq!(
+            rs.iter().map(|x| x.articles.len()).collect::<Vec<_>>(),
+            vec![3, 1, 1]
+        );
+        assert_eq!(rs[0].agencies.len(), 2);
+        assert_eq!(rs[0].min_price, "11억 5,000".parse().ok());
+        assert_eq!(rs[0].max_price, "12억".parse().ok());
+        assert_eq!(rs[2].key.as_ref().unwrap().unit, "5");
+    }
+}
@@ -0,0 +1,205 @@
+use futures::stream::{self, StreamExt, TryStreamExt};
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::estate::{CortarType, Error, EstateService, Region};
+
+pub const SEOUL: &str = "1100000000";
+pub const GANG_NAM_GU: &str = "1168000000";
+pub const SONG_PA_GU: &str = "1171000000";
+pub const SU_JUNG_GU: &str = "4113100000";
+pub const CHANG_GOK_DONG: &str = "4113110800";
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct RegionNode {
+    pub region: Region,
+    pub parent: String,
+    pub children: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct RegionTree {
+    pub root: String,
+    pub root_children: Vec<String>,
+    pub nodes: BTreeMap<String, RegionNode>,
+}
+
+impl RegionTree {
+    pub fn new(root: String) -> Self {
+        RegionTree {
+            root,
+            root_children: Vec::new(),
+            nodes: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, cortar_no: &str) -> Option<&Region> {
+        self.nodes.get(cortar_no).map(|x| &x.region)
+    }
+
+    pub fn parent(&self, cortar_no: &str) -> Option<&str> {
+        self.nodes.get(cortar_no).map(|x| x.parent.as_str())
+    }
+
+    pub fn children(&self, cortar_no: &str) -> Vec<&Region> {
+        let ids = if cortar_no == self.root {
+            &self.root_children
+        } else {
+            match self.nodes.get(cortar_no) {
+                Some(x) => &x.children,
+                None => return Vec::new(),
+            }
+        };
+        ids.iter().filter_map(|x| self.get(x)).collect()
+    }
+
+    // parent first, up to (but not including) the root
+    pub fn ancestors(&self, cortar_no: &str) -> Vec<&Region> {
+        let mut rs = Vec::new();
+        let mut cur = self.parent(cortar_no);
+        while let Some(x) = cur.and_then(|x| self.nodes.get(x)) {
+            rs.push(&x.region);
+            cur = Some(x.parent.as_str());
+        }
+        rs
+    }
+
+    // the dong level regions, which is what `complex_list` expects
+    pub fn leaves(&self) -> Vec<&Region> {
+        self.nodes
+            .values()
+            .filter(|x| x.region.cortar_type == CortarType::Section)
+            .map(|x| &x.region)
+            .collect()
+    }
+
+    // regions above the dong level that listed no sub-regions, their dongs are missing from `leaves`
+    pub fn dead_ends(&self) -> Vec<&Region> {
+        self.nodes
+            .values()
+            .filter(|x| x.region.cortar_type != CortarType::Section && x.children.is_empty())
+            .map(|x| &x.region)
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    fn insert(&mut self, parent: String, region: Region) -> bool {
+        if region.cortar_no == self.root || self.nodes.contains_key(&region.cortar_no) {
+            return false;
+        }
+        let children = if parent == self.root {
+            &mut self.root_children
+        } else {
+            match self.nodes.get_mut(&parent) {
+                Some(x) => &mut x.children,
+                None => return false,
+            }
+        };
+        children.push(region.cortar_no.clone());
+        self.nodes.insert(
+            region.cortar_no.clone(),
+            RegionNode {
+                region,
+                parent,
+                children: Vec::new(),
+            },
+        );
+        true
+    }
+}
+
+// a failing region_list call fails the whole tree
+pub async fn crawl_region_tree<T: EstateService>(
+    service: Arc<T>,
+    root: String,
+    concurrency: usize,
+) -> Result<RegionTree, Error> {
+    let mut tree = RegionTree::new(root.clone());
+    let mut frontier = vec![root];
+    while !frontier.is_empty() {
+        let results = stream::iter(frontier)
+            .map(|parent| {
+                let service = service.clone();
+                async move {
+                    let xs = service.region_list(parent.clone()).await?;
+                    Ok::<_, Error>((parent, xs))
+                }
+            })
+            .buffer_unordered(concurrency.max(1))
+            .try_collect::<Vec<_>>()
+            .await?;
+        frontier = Vec::new();
+        for (parent, xs) in results {
+            for x in xs {
+                let is_leaf = x.cortar_type == CortarType::Section;
+                let cortar_no = x.cortar_no.clone();
+                if tree.insert(parent.clone(), x) && !is_leaf {
+                    frontier.push(cortar_no);
+                }
+            }
+        }
+    }
+    Ok(tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::EstateServiceFake;
+
+    fn region(cortar_no: &str, cortar_type: &str) -> Region {
+        Region {
+            cortar_no: cortar_no.to_string(),
+            cortar_nm: cortar_no.to_string(),
+            cortar_type: CortarType::from_code(cortar_type),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn crawl_walks_down_to_dong() {
+        let dongs = vec![region("1168010300", "sec"), region("1168010600", "sec")];
+        let service = EstateServiceFake::new()
+            .with_regions(SEOUL, vec![region("1168000000", "dvsn")])
+            .with_regions("1168000000", dongs.clone())
+            // upstream returns the siblings for a dong, which must not recurse
+            .with_regions("1168010300", dongs);
+        let tree = crawl_region_tree(Arc::new(service), SEOUL.to_string(), 2)
+            .await
+            .unwrap();
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.leaves().len(), 2);
+        assert!(tree.dead_ends().is_empty());
+        assert_eq!(tree.parent("1168010600"), Some("1168000000"));
+        assert_eq!(tree.ancestors("1168010600").len(), 1);
+        assert_eq!(tree.children(SEOUL).len(), 1);
+        let json = tree.to_json().unwrap();
+        assert_eq!(serde_json::from_str::<RegionTree>(&json).unwrap(), tree);
+    }
+
+    #[tokio::test]
+    async fn empty_gu_is_not_a_dong() {
+        let service = EstateServiceFake::new()
+            .with_regions(
+                SEOUL,
+                vec![region(GANG_NAM_GU, "dvsn"), region(SONG_PA_GU, "dvsn")],
+            )
+            .with_regions(GANG_NAM_GU, vec![region("1168010300", "sec")]);
+        let tree = crawl_region_tree(Arc::new(service), SEOUL.to_string(), 2)
+            .await
+            .unwrap();
+        let ids = |xs: Vec<&Region>| xs.iter().map(|x| x.cortar_no.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(tree.leaves()), ["1168010300"]);
+        assert_eq!(ids(tree.dead_ends()), [SONG_PA_GU]);
+    }
+}
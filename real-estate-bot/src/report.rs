@@ -0,0 +1,296 @@
+use chrono::Local;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    estate::{Error, ListingWithComplex},
+    notify::ARTICLE_URL,
+    output::Tabular,
+    snapshot::SnapshotDiff,
+    stats::{summarize, SummaryLevel, SummaryRow},
+};
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }
+th { background: #f4f4f4; }
+.added { color: #0a7d28; }
+.removed { color: #b00020; text-decoration: line-through; }
+.changed { color: #a15c00; }
+";
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn or_empty(s: &Option<String>) -> String {
+    escape(s.as_deref().unwrap_or_default())
+}
+
+fn article_link(listing: &ListingWithComplex) -> String {
+    match &listing.article.atcl_no {
+        Some(x) => format!(
+            "<a href=\"{}/{}\">{}</a>",
+            ARTICLE_URL,
+            escape(x),
+            escape(x)
+        ),
+        None => String::new(),
+    }
+}
+
+fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut s = String::from("<table>\n<tr>");
+    for x in headers {
+        let _ = write!(s, "<th>{}</th>", escape(x));
+    }
+    s.push_str("</tr>\n");
+    for row in rows {
+        s.push_str("<tr>");
+        for x in row {
+            let _ = write!(s, "<td>{}</td>", x);
+        }
+        s.push_str("</tr>\n");
+    }
+    s.push_str("</table>\n");
+    s
+}
+
+fn listing_table(listings: &[&ListingWithComplex]) -> String {
+    let rows = listings
+        .iter()
+        .map(|x| {
+            let article = &x.article;
+            vec![
+                article_link(x),
+                or_empty(&article.trad_tp_nm),
+                or_empty(&article.prc_info),
+                or_empty(&article.spc2),
+                or_empty(&article.flr_info),
+                or_empty(&article.bild_nm),
+                or_empty(&article.rltr_nm),
+                or_empty(&article.cfm_ymd),
+            ]
+        })
+        .collect::<Vec<_>>();
+    table(
+        &[
+            "atclNo",
+            "trade",
+            "price",
+            "spc2",
+            "floor",
+            "building",
+            "agency",
+            "confirmed",
+        ],
+        &rows,
+    )
+}
+
+fn stats_table(rows: &[SummaryRow]) -> String {
+    // key and name are already in the section heading
+    let rows = rows
+        .iter()
+        .map(|x| x.row().into_iter().skip(2).map(|x| escape(&x)).collect())
+        .collect::<Vec<_>>();
+    table(&SummaryRow::headers()[2..], &rows)
+}
+
+fn changes(hscp_no: &str, diff: &SnapshotDiff) -> Vec<String> {
+    let mut rs = Vec::new();
+    let in_complex = |x: &&ListingWithComplex| x.complex.hscp_no == hscp_no;
+    for x in diff.added.iter().filter(in_complex) {
+        rs.push(format!(
+            "<li class=\"added\">신규 {} {} {}</li>",
+            article_link(x),
+            or_empty(&x.article.trad_tp_nm),
+            or_empty(&x.article.prc_info)
+        ));
+    }
+    for x in diff
+        .price_changed
+        .iter()
+        .filter(|x| x.entry.complex.hscp_no == hscp_no)
+    {
+        let show = |x: &Option<_>| match x {
+            Some(price) => format!("{}", price),
+            None => "-".to_string(),
+        };
+        rs.push(format!(
+            "<li class=\"changed\">가격변동 {} {} {} -&gt; {}</li>",
+            article_link(&x.entry),
+            or_empty(&x.entry.article.trad_tp_nm),
+            escape(&show(&x.old_price)),
+            escape(&show(&x.new_price))
+        ));
+    }
+    for x in diff.removed.iter().filter(in_complex) {
+        rs.push(format!(
+            "<li class=\"removed\">삭제 {} {} {}</li>",
+            escape(x.article.atcl_no.as_deref().unwrap_or_default()),
+            or_empty(&x.article.trad_tp_nm),
+            or_empty(&x.article.prc_info)
+        ));
+    }
+    rs
+}
+
+// a single html page with one section per complex, `diff` adds the changes since the last run
+pub fn render_report(
+    title: &str,
+    listings: &[ListingWithComplex],
+    diff: Option<&SnapshotDiff>,
+) -> String {
+    let mut complexes: BTreeMap<&str, Vec<&ListingWithComplex>> = BTreeMap::new();
+    for x in listings {
+        complexes.entry(&x.complex.hscp_no).or_default().push(x);
+    }
+    // complexes whose listings are all gone still show up with their removals
+    for x in diff.iter().flat_map(|x| x.removed.iter()) {
+        complexes.entry(&x.complex.hscp_no).or_default();
+    }
+    let summaries = summarize(listings, SummaryLevel::Complex);
+
+    let mut s = String::new();
+    let _ = write!(
+        s,
+        "<!DOCTYPE html>\n<html lang=\"ko\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n<p>{} · {} complexes · {} listings</p>\n",
+        escape(title),
+        STYLE,
+        escape(title),
+        Local::now().format("%Y-%m-%d %H:%M"),
+        complexes.len(),
+        listings.len()
+    );
+    if let Some(diff) = diff {
+        let _ = writeln!(
+            s,
+            "<p>{} new, {} removed, {} price changed since the last run</p>",
+            diff.added.len(),
+            diff.removed.len(),
+            diff.price_changed.len()
+        );
+    }
+    for (hscp_no, xs) in complexes.iter() {
+        let name = xs
+            .first()
+            .map(|x| x.complex.hscp_nm.clone())
+            .or_else(|| {
+                diff?
+                    .removed
+                    .iter()
+                    .find(|x| x.complex.hscp_no == *hscp_no)
+                    .map(|x| x.complex.hscp_nm.clone())
+            })
+            .unwrap_or_default();
+        let _ = writeln!(
+            s,
+            "<section id=\"complex-{}\">\n<h2>{} <small>{}</small></h2>",
+            escape(hscp_no),
+            escape(&name),
+            escape(hscp_no)
+        );
+        let rows = summaries
+            .iter()
+            .filter(|x| x.key == *hscp_no)
+            .flat_map(|x| x.rows())
+            .collect::<Vec<_>>();
+        if !rows.is_empty() {
+            s.push_str("<h3>Prices</h3>\n");
+            s.push_str(&stats_table(&rows));
+        }
+        if let Some(diff) = diff {
+            let xs = changes(hscp_no, diff);
+            if !xs.is_empty() {
+                let _ = writeln!(s, "<h3>Changes</h3>\n<ul>\n{}\n</ul>", xs.join("\n"));
+            }
+        }
+        if !xs.is_empty() {
+            s.push_str("<h3>Listings</h3>\n");
+            s.push_str(&listing_table(xs));
+        }
+        s.push_str("</section>\n");
+    }
+    s.push_str("</body>\n</html>\n");
+    s
+}
+
+// writes index.html into `dir`, creating it when missing
+pub fn write_report<P: AsRef<Path>>(
+    dir: P,
+    title: &str,
+    listings: &[ListingWithComplex],
+    diff: Option<&SnapshotDiff>,
+) -> Result<PathBuf, Error> {
+    fs::create_dir_all(&dir)?;
+    let path = dir.as_ref().join("index.html");
+    fs::write(&path, render_report(title, listings, diff))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        estate::{Complex, ComplexArticle},
+        snapshot::PriceChange,
+    };
+
+    fn listing(hscp_no: &str, atcl_no: &str, prc_info: &str) -> ListingWithComplex {
+        ListingWithComplex::new(
+            Complex {
+                hscp_no: hscp_no.to_string(),
+                hscp_nm: format!("<단지 {}>", hscp_no),
+                ..Default::default()
+            },
+            ComplexArticle {
+                atcl_no: Some(atcl_no.to_string()),
+                trad_tp_nm: Some("매매".to_string()),
+                spc2: Some("84.9".to_string()),
+                prc_info: Some(prc_info.to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn report_sections() {
+        let listings = vec![listing("8928", "1", "12억"), listing("8928", "2", "10억")];
+        let diff = SnapshotDiff {
+            added: vec![listing("8928", "2", "10억")],
+            removed: vec![listing("1", "3", "5억")],
+            price_changed: vec![PriceChange {
+                entry: listing("8928", "1", "12억"),
+                old_price: "12억 5,000".parse().ok(),
+                new_price: "12억".parse().ok(),
+            }],
+        };
+        let html = render_report("창곡동", &listings, Some(&diff));
+        assert!(html.contains("<section id=\"complex-8928\">"));
+        assert!(html.contains("<section id=\"complex-1\">"));
+        assert!(html.contains("&lt;단지 8928&gt;"));
+        assert!(html.contains("<a href=\"https://m.land.naver.com/article/info/1\">1</a>"));
+        assert!(html.contains("12억 5,000 -&gt; 12억"));
+        assert!(html.contains("<td>11억</td>"));
+    }
+
+    #[test]
+    fn write_index() {
+        let dir =
+            std::env::temp_dir().join(format!("real-estate-bot-report-{}", std::process::id()));
+        let path = write_report(&dir, "report", &[listing("8928", "1", "12억")], None).unwrap();
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("<h3>Listings</h3>"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
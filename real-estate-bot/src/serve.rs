@@ -0,0 +1,375 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    future::Future,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use crate::{
+    estate::{Complex, Error, ListingWithComplex, Price},
+    filter::ListingFilter,
+    snapshot::{diff, Snapshot, SnapshotStore},
+    watch::region_contains,
+};
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: String) -> Self {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            message,
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(e: Error) -> Self {
+        ApiError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: e.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({ "error": self.message }));
+        (self.status, body).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<Page<T>>, ApiError>;
+
+// ?page= is 1-based, ?size= defaults to 50 and is capped at 500
+#[derive(Debug, Clone, Copy, Default, serde_derive::Deserialize)]
+pub struct PageParams {
+    page: Option<usize>,
+    size: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: usize,
+    pub size: usize,
+    pub total: usize,
+}
+
+impl<T: Clone> Page<&T> {
+    fn cloned(self) -> Page<T> {
+        Page {
+            items: self.items.into_iter().cloned().collect(),
+            page: self.page,
+            size: self.size,
+            total: self.total,
+        }
+    }
+}
+
+impl<T> Page<T> {
+    fn of(xs: Vec<T>, params: PageParams) -> Self {
+        let page = params.page.unwrap_or(1).max(1);
+        let size = params
+            .size
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let total = xs.len();
+        Page {
+            items: xs
+                .into_iter()
+                .skip(page.saturating_sub(1).saturating_mul(size))
+                .take(size)
+                .collect(),
+            page,
+            size,
+            total,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegionSummary {
+    pub region_no: String,
+    pub taken_at: DateTime<Utc>,
+    pub complexes: usize,
+    pub listings: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde_derive::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    PriceChanged,
+}
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    pub region_no: String,
+    pub taken_at: DateTime<Utc>,
+    pub kind: ChangeKind,
+    pub listing: ListingWithComplex,
+    pub old_price: Option<Price>,
+    pub new_price: Option<Price>,
+}
+
+#[derive(Debug, serde_derive::Deserialize)]
+struct ComplexParams {
+    region: Option<String>,
+}
+
+#[derive(Debug, serde_derive::Deserialize)]
+struct ArticleParams {
+    complex: Option<String>,
+    // e.g. "9억", "9억 5,000" or 만원 as a plain number
+    min_price: Option<String>,
+    max_price: Option<String>,
+}
+
+#[derive(Debug, serde_derive::Deserialize)]
+struct ChangeParams {
+    // rfc 3339 or yyyy-mm-dd
+    since: Option<String>,
+}
+
+// everything the handlers answer from, rebuilt only when the snapshot file changes
+#[derive(Debug)]
+struct Loaded {
+    // the most recent snapshot of every series, see `Snapshot::series`
+    latest: Vec<Snapshot>,
+    // changes between consecutive snapshots of every series, newest first
+    changes: Vec<Change>,
+}
+
+impl Loaded {
+    fn new(snapshots: Vec<Snapshot>) -> Self {
+        let mut series: BTreeMap<String, Vec<Snapshot>> = BTreeMap::new();
+        for x in snapshots {
+            series.entry(x.series().to_string()).or_default().push(x);
+        }
+        let mut latest = Vec::new();
+        let mut changes = Vec::new();
+        for (_, mut xs) in series {
+            xs.sort_by_key(|x| x.taken_at);
+            for pair in xs.windows(2) {
+                let (prev, cur) = (&pair[0], &pair[1]);
+                let change = |kind, listing, old_price, new_price| Change {
+                    region_no: cur.region_no.clone(),
+                    taken_at: cur.taken_at,
+                    kind,
+                    listing,
+                    old_price,
+                    new_price,
+                };
+                let d = diff(prev, cur);
+                changes.extend(d.added.into_iter().map(|x| {
+                    let price = x.article.price();
+                    change(ChangeKind::Added, x, None, price)
+                }));
+                changes.extend(d.removed.into_iter().map(|x| {
+                    let price = x.article.price();
+                    change(ChangeKind::Removed, x, price, None)
+                }));
+                changes.extend(
+                    d.price_changed.into_iter().map(|x| {
+                        change(ChangeKind::PriceChanged, x.entry, x.old_price, x.new_price)
+                    }),
+                );
+            }
+            latest.extend(xs.pop());
+        }
+        changes.sort_by_key(|x| Reverse(x.taken_at));
+        Loaded { latest, changes }
+    }
+}
+
+// modification time and length of the snapshot file, None while it doesn't exist
+type FileVersion = Option<(SystemTime, u64)>;
+
+#[derive(Debug)]
+struct ServeState {
+    store: SnapshotStore,
+    cache: Mutex<Option<(FileVersion, Arc<Loaded>)>>,
+}
+
+impl ServeState {
+    fn version(&self) -> Result<FileVersion, Error> {
+        match fs::metadata(self.store.path()) {
+            Ok(x) => Ok(Some((x.modified()?, x.len()))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // blocking, parses the whole file when it changed since the last call
+    fn load(&self) -> Result<Arc<Loaded>, Error> {
+        let version = self.version()?;
+        if let Some((cached, loaded)) = self.cache.lock().unwrap().as_ref() {
+            if *cached == version {
+                return Ok(loaded.clone());
+            }
+        }
+        let loaded = Arc::new(Loaded::new(self.store.load_all()?));
+        *self.cache.lock().unwrap() = Some((version, loaded.clone()));
+        Ok(loaded)
+    }
+}
+
+async fn load(state: Arc<ServeState>) -> Result<Arc<Loaded>, ApiError> {
+    let loaded = tokio::task::spawn_blocking(move || state.load())
+        .await
+        .map_err(|e| Error::ServeError(e.to_string()))??;
+    Ok(loaded)
+}
+
+fn parse_price(s: &Option<String>) -> Result<Option<Price>, ApiError> {
+    s.as_ref()
+        .map(|x| {
+            x.parse::<Price>()
+                .map_err(|e| ApiError::bad_request(e.to_string()))
+        })
+        .transpose()
+}
+
+fn parse_since(s: &str) -> Result<DateTime<Utc>, ApiError> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|x| x.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(|x| x.and_hms_opt(0, 0, 0).unwrap().and_utc())
+        })
+        .map_err(|_| ApiError::bad_request(format!("invalid since: {}", s)))
+}
+
+async fn regions(
+    State(state): State<Arc<ServeState>>,
+    Query(page): Query<PageParams>,
+) -> ApiResult<RegionSummary> {
+    let loaded = load(state).await?;
+    let xs = loaded
+        .latest
+        .iter()
+        .map(|x| RegionSummary {
+            complexes: x
+                .entries
+                .iter()
+                .map(|y| &y.complex.hscp_no)
+                .collect::<BTreeSet<_>>()
+                .len(),
+            listings: x.entries.len(),
+            region_no: x.region_no.clone(),
+            taken_at: x.taken_at,
+        })
+        .collect();
+    Ok(Json(Page::of(xs, page)))
+}
+
+async fn complexes(
+    State(state): State<Arc<ServeState>>,
+    Query(params): Query<ComplexParams>,
+    Query(page): Query<PageParams>,
+) -> ApiResult<Complex> {
+    let loaded = load(state).await?;
+    let mut rs: BTreeMap<&str, &Complex> = BTreeMap::new();
+    for snapshot in loaded.latest.iter() {
+        for x in snapshot.entries.iter() {
+            let in_region = params.region.as_ref().is_none_or(|region| {
+                *region == snapshot.region_no || region_contains(region, &x.complex.cortar_no)
+            });
+            if in_region {
+                rs.entry(&x.complex.hscp_no).or_insert(&x.complex);
+            }
+        }
+    }
+    Ok(Json(Page::of(rs.into_values().collect(), page).cloned()))
+}
+
+async fn articles(
+    State(state): State<Arc<ServeState>>,
+    Query(params): Query<ArticleParams>,
+    Query(page): Query<PageParams>,
+) -> ApiResult<ListingWithComplex> {
+    let filter = ListingFilter {
+        min_price: parse_price(&params.min_price)?.map(|x| x.amount),
+        max_price: parse_price(&params.max_price)?.map(|x| x.amount),
+        ..Default::default()
+    };
+    let loaded = load(state).await?;
+    let mut rs = BTreeMap::new();
+    for x in loaded.latest.iter().flat_map(|x| x.entries.iter()) {
+        let in_complex = params
+            .complex
+            .as_ref()
+            .is_none_or(|y| *y == x.complex.hscp_no);
+        if let Some(key) = x.key().filter(|_| in_complex && filter.matches(&x.article)) {
+            rs.entry(key).or_insert(x);
+        }
+    }
+    Ok(Json(Page::of(rs.into_values().collect(), page).cloned()))
+}
+
+async fn changes(
+    State(state): State<Arc<ServeState>>,
+    Query(params): Query<ChangeParams>,
+    Query(page): Query<PageParams>,
+) -> ApiResult<Change> {
+    let since = params.since.as_deref().map(parse_since).transpose()?;
+    let loaded = load(state).await?;
+    let xs = loaded
+        .changes
+        .iter()
+        .take_while(|x| since.is_none_or(|since| x.taken_at >= since))
+        .collect();
+    Ok(Json(Page::of(xs, page).cloned()))
+}
+
+pub fn router(store: SnapshotStore) -> Router {
+    let state = ServeState {
+        store,
+        cache: Mutex::new(None),
+    };
+    Router::new()
+        .route("/regions", get(regions))
+        .route("/complexes", get(complexes))
+        .route("/articles", get(articles))
+        .route("/changes", get(changes))
+        .with_state(Arc::new(state))
+}
+
+// the snapshot file is re-read whenever it changes, so a running crawl shows up without a restart
+pub async fn serve<F: Future<Output = ()>>(
+    addr: SocketAddr,
+    store: SnapshotStore,
+    shutdown: F,
+) -> Result<(), Error> {
+    let server = axum::Server::try_bind(&addr)
+        .map_err(|e| Error::ServeError(e.to_string()))?
+        .serve(router(store).into_make_service());
+    println!("listening on {}", server.local_addr());
+    server
+        .with_graceful_shutdown(shutdown)
+        .await
+        .map_err(|e| Error::ServeError(e.to_string()))
+}
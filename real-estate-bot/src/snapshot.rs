@@ -0,0 +1,343 @@
+use chrono::{DateTime, Utc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    crawl::CrawlReport,
+    estate::{ComplexArticle, Error, ListingKey, ListingWithComplex, Price},
+    property::PropertyKey,
+    watch::region_contains,
+};
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct Snapshot {
+    pub region_no: String,
+    pub taken_at: DateTime<Utc>,
+    pub entries: Vec<ListingWithComplex>,
+    // complexes that could not be fetched, their listings are not reported as removed
+    #[serde(default)]
+    pub failed_complex_ids: Vec<String>,
+    // dongs whose complex list could not be fetched, same for every complex in them
+    #[serde(default)]
+    pub failed_region_nos: Vec<String>,
+    // the daemon job that took it, jobs crawling the same region are diffed separately
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job: Option<String>,
+}
+
+impl Snapshot {
+    pub fn new(region_no: String, entries: Vec<ListingWithComplex>) -> Self {
+        Snapshot {
+            region_no,
+            taken_at: Utc::now(),
+            entries,
+            failed_complex_ids: Vec::new(),
+            failed_region_nos: Vec::new(),
+            job: None,
+        }
+    }
+
+    // snapshots of the same series are compared with each other: the job's, or the region's
+    pub fn series(&self) -> &str {
+        self.job.as_deref().unwrap_or(&self.region_no)
+    }
+
+    pub fn from_report(region_no: String, report: CrawlReport) -> Self {
+        Snapshot {
+            failed_complex_ids: report.failed_complex_ids(),
+            failed_region_nos: report.failed_region_nos(),
+            ..Self::new(region_no, report.entries)
+        }
+    }
+
+    fn by_key(&self) -> BTreeMap<ListingKey, &ListingWithComplex> {
+        self.entries
+            .iter()
+            .filter_map(|x| x.key().map(|k| (k, x)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize)]
+pub struct PriceChange {
+    pub entry: ListingWithComplex,
+    pub old_price: Option<Price>,
+    pub new_price: Option<Price>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, serde_derive::Serialize)]
+pub struct SnapshotDiff {
+    pub added: Vec<ListingWithComplex>,
+    pub removed: Vec<ListingWithComplex>,
+    pub price_changed: Vec<PriceChange>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.price_changed.is_empty()
+    }
+}
+
+pub(crate) fn price_changed(old: &ComplexArticle, new: &ComplexArticle) -> bool {
+    match (old.price(), new.price()) {
+        (None, None) => old.prc_info != new.prc_info,
+        (x, y) => x != y,
+    }
+}
+
+fn properties(snapshot: &Snapshot) -> BTreeSet<PropertyKey> {
+    snapshot
+        .entries
+        .iter()
+        .filter_map(PropertyKey::of)
+        .collect()
+}
+
+// true the first time a unit is seen, an entry without a key is a unit of its own
+fn first_of_unit(seen: &mut BTreeSet<PropertyKey>, entry: &ListingWithComplex) -> bool {
+    PropertyKey::of(entry).is_none_or(|x| seen.insert(x))
+}
+
+// keyed by unit rather than by ad: a new ad for a unit that was already listed by another agency
+// is not reported as added, nor is a withdrawn ad for a unit that is still listed reported as
+// removed, and a unit listed by several ads shows up once in each list
+pub fn diff(prev: &Snapshot, cur: &Snapshot) -> SnapshotDiff {
+    let failed = |entry: &ListingWithComplex| {
+        cur.failed_complex_ids.contains(&entry.complex.hscp_no)
+            || cur
+                .failed_region_nos
+                .iter()
+                .any(|x| region_contains(x, &entry.complex.cortar_no))
+    };
+    let prev_properties = properties(prev);
+    let cur_properties = properties(cur);
+    let listed = |properties: &BTreeSet<PropertyKey>, entry: &ListingWithComplex| {
+        PropertyKey::of(entry).is_some_and(|x| properties.contains(&x))
+    };
+    let prev = prev.by_key();
+    let cur = cur.by_key();
+    let mut rs = SnapshotDiff::default();
+    let (mut added, mut changed, mut removed) = (BTreeSet::new(), BTreeSet::new(), BTreeSet::new());
+    for (key, entry) in cur.iter() {
+        match prev.get(key) {
+            None if listed(&prev_properties, entry) => (),
+            None if first_of_unit(&mut added, entry) => rs.added.push((*entry).clone()),
+            None => (),
+            Some(old)
+                if price_changed(&old.article, &entry.article)
+                    && first_of_unit(&mut changed, entry) =>
+            {
+                rs.price_changed.push(PriceChange {
+                    entry: (*entry).clone(),
+                    old_price: old.article.price(),
+                    new_price: entry.article.price(),
+                })
+            }
+            Some(_) => (),
+        }
+    }
+    rs.removed = prev
+        .iter()
+        .filter(|(key, entry)| {
+            !cur.contains_key(key)
+                && !failed(entry)
+                && !listed(&cur_properties, entry)
+                && first_of_unit(&mut removed, entry)
+        })
+        .map(|(_, entry)| (*entry).clone())
+        .collect();
+    rs
+}
+
+// one json encoded `Snapshot` per line
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    path: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        SnapshotStore { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // the most recent snapshot of every series is also kept in a file next to the history,
+    // so a crawl doesn't parse every snapshot ever taken to find its previous run
+    fn latest_path(&self) -> PathBuf {
+        self.path.with_extension("latest.json")
+    }
+
+    fn load_latest(&self) -> Result<BTreeMap<String, Snapshot>, Error> {
+        match fs::read_to_string(self.latest_path()) {
+            Ok(x) => Ok(serde_json::from_str(&x)?),
+            // a store written before the latest file existed, rebuilt from the history once
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut rs: BTreeMap<String, Snapshot> = BTreeMap::new();
+                for x in self.load_all()? {
+                    if rs.get(x.series()).is_none_or(|y| y.taken_at <= x.taken_at) {
+                        rs.insert(x.series().to_string(), x);
+                    }
+                }
+                Ok(rs)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // written to a temp file and renamed so a reader never sees half of it
+    fn write_latest(&self, latest: &BTreeMap<String, Snapshot>) -> Result<(), Error> {
+        let tmp = self.path.with_extension("latest.json.tmp");
+        fs::write(&tmp, serde_json::to_string(latest)?)?;
+        fs::rename(&tmp, self.latest_path())?;
+        Ok(())
+    }
+
+    pub fn append(&self, snapshot: &Snapshot) -> Result<(), Error> {
+        let mut latest = self.load_latest()?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(snapshot)?)?;
+        if latest
+            .get(snapshot.series())
+            .is_none_or(|x| x.taken_at <= snapshot.taken_at)
+        {
+            latest.insert(snapshot.series().to_string(), snapshot.clone());
+            self.write_latest(&latest)?;
+        }
+        Ok(())
+    }
+
+    pub fn load_all(&self) -> Result<Vec<Snapshot>, Error> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        BufReader::new(File::open(&self.path)?)
+            .lines()
+            .filter(|x| x.as_ref().map(|x| !x.trim().is_empty()).unwrap_or(true))
+            .map(|x| Ok(serde_json::from_str(&x?)?))
+            .collect()
+    }
+
+    pub fn latest(&self, series: &str) -> Result<Option<Snapshot>, Error> {
+        Ok(self.load_latest()?.remove(series))
+    }
+
+    // stores `snapshot` and returns the changes since the previous one of the same series
+    pub fn record(&self, snapshot: &Snapshot) -> Result<Option<SnapshotDiff>, Error> {
+        let prev = self.latest(snapshot.series())?;
+        self.append(snapshot)?;
+        Ok(prev.map(|prev| diff(&prev, snapshot)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::estate::Complex;
+
+    fn entry(hscp_no: &str, atcl_no: &str, prc_info: &str) -> ListingWithComplex {
+        ListingWithComplex::new(
+            Complex {
+                hscp_no: hscp_no.to_string(),
+                cortar_no: "4113110800".to_string(),
+                ..Default::default()
+            },
+            ComplexArticle {
+                atcl_no: Some(atcl_no.to_string()),
+                prc_info: Some(prc_info.to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn diff_snapshots() {
+        let prev = Snapshot::new(
+            "4113110800".to_string(),
+            vec![entry("8928", "1", "12억"), entry("8928", "2", "10억")],
+        );
+        let cur = Snapshot::new(
+            "4113110800".to_string(),
+            vec![entry("8928", "2", "9억 5,000"), entry("8928", "3", "11억")],
+        );
+        let rs = diff(&prev, &cur);
+        assert_eq!(rs.added, vec![entry("8928", "3", "11억")]);
+        assert_eq!(rs.removed, vec![entry("8928", "1", "12억")]);
+        assert_eq!(rs.price_changed.len(), 1);
+        assert_eq!(rs.price_changed[0].old_price, "10억".parse().ok());
+        assert_eq!(rs.price_changed[0].new_price, "9억 5,000".parse().ok());
+        assert!(diff(&cur, &cur).is_empty());
+
+        let failed = Snapshot {
+            failed_complex_ids: vec!["8928".to_string()],
+            ..Snapshot::new("4113110800".to_string(), Vec::new())
+        };
+        assert!(diff(&cur, &failed).is_empty());
+
+        let failed = Snapshot {
+            failed_region_nos: vec!["4113110800".to_string()],
+            ..Snapshot::new("4113100000".to_string(), Vec::new())
+        };
+        assert!(diff(&cur, &failed).is_empty());
+    }
+
+    #[test]
+    fn diff_units_instead_of_ads() {
+        let ad = |atcl_no: &str| ListingWithComplex {
+            article: ComplexArticle {
+                same_addr_hash: Some("a".to_string()),
+                ..entry("8928", atcl_no, "12억").article
+            },
+            ..entry("8928", atcl_no, "12억")
+        };
+        let prev = Snapshot::new("4113110800".to_string(), vec![ad("1"), ad("2")]);
+        let cur = Snapshot::new("4113110800".to_string(), vec![ad("2"), ad("3")]);
+        assert!(diff(&prev, &cur).is_empty());
+        let gone = Snapshot::new("4113110800".to_string(), Vec::new());
+        assert_eq!(diff(&cur, &gone).removed.len(), 1);
+        assert_eq!(diff(&gone, &cur).added.len(), 1);
+    }
+
+    #[test]
+    fn store_roundtrip() {
+        let path = std::env::temp_dir().join(format!(
+            "real-estate-bot-snapshot-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("latest.json"));
+        let store = SnapshotStore::new(&path);
+        let first = Snapshot::new("1".to_string(), vec![entry("8928", "1", "12억")]);
+        assert_eq!(store.record(&first).unwrap(), None);
+        let second = Snapshot::new("1".to_string(), vec![entry("8928", "1", "11억")]);
+        let rs = store.record(&second).unwrap().unwrap();
+        assert_eq!(rs.price_changed.len(), 1);
+        assert_eq!(store.load_all().unwrap().len(), 2);
+        assert_eq!(store.latest("1").unwrap(), Some(second.clone()));
+
+        // another job on the same region doesn't diff against the region's snapshots
+        let job = Snapshot {
+            job: Some("leases".to_string()),
+            ..Snapshot::new("1".to_string(), Vec::new())
+        };
+        assert_eq!(store.record(&job).unwrap(), None);
+        assert_eq!(store.latest("1").unwrap(), Some(second.clone()));
+
+        // a history without the latest file still finds its previous snapshot
+        std::fs::remove_file(store.latest_path()).unwrap();
+        let third = Snapshot::new("1".to_string(), vec![entry("8928", "1", "10억")]);
+        let rs = store.record(&third).unwrap().unwrap();
+        assert_eq!(rs.price_changed[0].old_price, "11억".parse().ok());
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(store.latest_path()).unwrap();
+    }
+}
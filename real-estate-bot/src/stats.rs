@@ -0,0 +1,362 @@
+use bigdecimal::{BigDecimal, ToPrimitive};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    str::FromStr,
+};
+
+use crate::{
+    estate::{ListingWithComplex, Price},
+    output::Tabular,
+    property::PropertyKey,
+};
+
+pub const M2_PER_PYEONG: f64 = 3.305785;
+
+// exclusive area (spc2) bands used by the housing statistics
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+pub enum AreaBand {
+    Under60,
+    From60To85,
+    From85To135,
+    Over135,
+}
+
+impl AreaBand {
+    pub fn of(area: f64) -> AreaBand {
+        if area < 60.0 {
+            AreaBand::Under60
+        } else if area < 85.0 {
+            AreaBand::From60To85
+        } else if area < 135.0 {
+            AreaBand::From85To135
+        } else {
+            AreaBand::Over135
+        }
+    }
+}
+
+impl fmt::Display for AreaBand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            AreaBand::Under60 => "~60㎡",
+            AreaBand::From60To85 => "60~85㎡",
+            AreaBand::From85To135 => "85~135㎡",
+            AreaBand::Over135 => "135㎡~",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde_derive::Serialize)]
+pub enum SummaryLevel {
+    Complex,
+    Dong,
+    Gu,
+}
+
+impl FromStr for SummaryLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "complex" => Ok(SummaryLevel::Complex),
+            "dong" => Ok(SummaryLevel::Dong),
+            "gu" => Ok(SummaryLevel::Gu),
+            _ => Err(format!("unknown summary level: {}", s)),
+        }
+    }
+}
+
+impl SummaryLevel {
+    fn key(&self, listing: &ListingWithComplex) -> String {
+        let cortar_no = &listing.complex.cortar_no;
+        match self {
+            SummaryLevel::Complex => listing.complex.hscp_no.clone(),
+            SummaryLevel::Dong => cortar_no.clone(),
+            // a short or non-ascii cortarNo is kept as it is
+            SummaryLevel::Gu => match cortar_no.get(..5) {
+                Some(x) => format!("{}00000", x),
+                None => cortar_no.clone(),
+            },
+        }
+    }
+}
+
+// amounts are in 만원, unit prices in 만원 per ㎡ / 평 of exclusive area
+#[derive(Debug, Clone, Default, PartialEq, serde_derive::Serialize)]
+pub struct PriceStats {
+    pub count: usize,
+    // distinct units, ads sharing sameAddrHash count once
+    pub units: usize,
+    pub min: Option<BigDecimal>,
+    pub median: Option<BigDecimal>,
+    pub max: Option<BigDecimal>,
+    pub median_per_m2: Option<f64>,
+    pub median_per_pyeong: Option<f64>,
+}
+
+fn median<T: Clone>(xs: &[T], mean: impl Fn(&T, &T) -> T) -> Option<T> {
+    let n = xs.len();
+    match n {
+        0 => None,
+        _ if n % 2 == 1 => Some(xs[n / 2].clone()),
+        _ => Some(mean(&xs[n / 2 - 1], &xs[n / 2])),
+    }
+}
+
+impl PriceStats {
+    pub fn from_listings<'a, I: IntoIterator<Item = &'a ListingWithComplex>>(listings: I) -> Self {
+        let listings = listings
+            .into_iter()
+            .filter(|x| x.article.price().is_some())
+            .collect::<Vec<_>>();
+        let xs = listings.iter().map(|x| &x.article).collect::<Vec<_>>();
+        let mut prices = xs
+            .iter()
+            .filter_map(|x| x.price().map(|p: Price| p.amount))
+            .collect::<Vec<_>>();
+        prices.sort();
+        let mut unit_prices = xs
+            .iter()
+            .filter_map(|x| {
+                let price = x.price()?.amount.to_f64()?;
+                x.exclusive_area()
+                    .filter(|area| *area > 0.0)
+                    .map(|area| price / area)
+            })
+            .collect::<Vec<_>>();
+        unit_prices.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let median_per_m2 = median(&unit_prices, |a, b| (a + b) / 2.0);
+        // grouped like `property::consolidate`, an ad without a key is a unit of its own
+        let keys = listings
+            .iter()
+            .map(|x| PropertyKey::of(x))
+            .collect::<Vec<_>>();
+        let units = keys.iter().flatten().collect::<BTreeSet<_>>().len()
+            + keys.iter().filter(|x| x.is_none()).count();
+        PriceStats {
+            count: prices.len(),
+            units,
+            min: prices.first().cloned(),
+            median: median(&prices, |a, b| (a + b) / BigDecimal::from(2)),
+            max: prices.last().cloned(),
+            median_per_m2,
+            median_per_pyeong: median_per_m2.map(|x| x * M2_PER_PYEONG),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize)]
+pub struct MarketSummary {
+    pub level: SummaryLevel,
+    pub key: String,
+    pub name: String,
+    pub trade_type: String,
+    pub stats: PriceStats,
+    pub area_bands: BTreeMap<AreaBand, PriceStats>,
+}
+
+// one summary per (level key, trade type)
+pub fn summarize(listings: &[ListingWithComplex], level: SummaryLevel) -> Vec<MarketSummary> {
+    let mut groups: BTreeMap<(String, String), Vec<&ListingWithComplex>> = BTreeMap::new();
+    for x in listings {
+        let trade_type = x.article.trad_tp_nm.clone().unwrap_or_default();
+        groups
+            .entry((level.key(x), trade_type))
+            .or_default()
+            .push(x);
+    }
+    groups
+        .into_iter()
+        .map(|((key, trade_type), xs)| {
+            let mut bands: BTreeMap<AreaBand, Vec<&ListingWithComplex>> = BTreeMap::new();
+            for x in xs.iter() {
+                if let Some(area) = x.article.exclusive_area() {
+                    bands.entry(AreaBand::of(area)).or_default().push(*x);
+                }
+            }
+            let name = match level {
+                SummaryLevel::Complex => xs[0].complex.hscp_nm.clone(),
+                _ => key.clone(),
+            };
+            MarketSummary {
+                level,
+                key,
+                name,
+                trade_type,
+                stats: PriceStats::from_listings(xs.iter().copied()),
+                area_bands: bands
+                    .into_iter()
+                    .map(|(band, xs)| (band, PriceStats::from_listings(xs)))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+// a flattened `MarketSummary` line for table / csv output
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize)]
+pub struct SummaryRow {
+    pub key: String,
+    pub name: String,
+    pub trade_type: String,
+    pub area_band: Option<AreaBand>,
+    pub stats: PriceStats,
+}
+
+impl MarketSummary {
+    pub fn rows(&self) -> Vec<SummaryRow> {
+        let row = |area_band, stats: &PriceStats| SummaryRow {
+            key: self.key.clone(),
+            name: self.name.clone(),
+            trade_type: self.trade_type.clone(),
+            area_band,
+            stats: stats.clone(),
+        };
+        let mut rs = vec![row(None, &self.stats)];
+        rs.extend(
+            self.area_bands
+                .iter()
+                .map(|(band, stats)| row(Some(*band), stats)),
+        );
+        rs
+    }
+}
+
+pub(crate) fn show_price(x: &Option<BigDecimal>) -> String {
+    x.clone()
+        .map(|amount| Price::new(amount).to_string())
+        .unwrap_or_default()
+}
+
+pub(crate) fn show_unit_price(x: &Option<f64>) -> String {
+    x.map(|x| format!("{:.0}", x)).unwrap_or_default()
+}
+
+impl Tabular for SummaryRow {
+    fn headers() -> Vec<&'static str> {
+        vec![
+            "key", "name", "trade", "area", "count", "units", "min", "median", "max", "per ㎡",
+            "per 평",
+        ]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.key.clone(),
+            self.name.clone(),
+            self.trade_type.clone(),
+            self.area_band
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "all".to_string()),
+            self.stats.count.to_string(),
+            self.stats.units.to_string(),
+            show_price(&self.stats.min),
+            show_price(&self.stats.median),
+            show_price(&self.stats.max),
+            show_unit_price(&self.stats.median_per_m2),
+            show_unit_price(&self.stats.median_per_pyeong),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::estate::{Complex, ComplexArticle};
+
+    fn listing(hscp_no: &str, cortar_no: &str, spc2: &str, prc_info: &str) -> ListingWithComplex {
+        ListingWithComplex::new(
+            Complex {
+                hscp_no: hscp_no.to_string(),
+                hscp_nm: format!("complex {}", hscp_no),
+                cortar_no: cortar_no.to_string(),
+                ..Default::default()
+            },
+            ComplexArticle {
+                trad_tp_nm: Some("매매".to_string()),
+                spc2: Some(spc2.to_string()),
+                prc_info: Some(prc_info.to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn price_stats() {
+        let xs = [
+            listing("1", "1168010300", "50", "5억"),
+            listing("1", "1168010300", "100", "12억"),
+            listing("1", "1168010300", "100", "10억"),
+            listing("1", "1168010300", "100", "협의"),
+        ];
+        let stats = PriceStats::from_listings(&xs);
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.min, Some(BigDecimal::from(50000)));
+        assert_eq!(stats.median, Some(BigDecimal::from(100000)));
+        assert_eq!(stats.max, Some(BigDecimal::from(120000)));
+        assert_eq!(stats.median_per_m2, Some(1000.0));
+
+        let stats = PriceStats::from_listings(&xs[1..3]);
+        assert_eq!(stats.median, Some(BigDecimal::from(110000)));
+    }
+
+    #[test]
+    fn units_follow_property_key() {
+        let ad = |atcl_no: &str, same_addr_hash: &str| {
+            let mut x = listing("1", "1168010300", "84", "20억");
+            x.article.atcl_no = Some(atcl_no.to_string());
+            x.article.same_addr_hash = Some(same_addr_hash.to_string());
+            x
+        };
+        let xs = [ad("1", "h1"), ad("2", "h1"), ad("3", ""), ad("4", "")];
+        let stats = PriceStats::from_listings(&xs);
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.units, 3);
+    }
+
+    #[test]
+    fn roll_up() {
+        let xs = vec![
+            listing("1", "1168010300", "84", "20억"),
+            listing("2", "1168010300", "59", "15억"),
+            listing("3", "1168010600", "84", "25억"),
+            listing("4", "1171010100", "84", "18억"),
+        ];
+        assert_eq!(summarize(&xs, SummaryLevel::Complex).len(), 4);
+        let dong = summarize(&xs, SummaryLevel::Dong);
+        assert_eq!(dong.len(), 3);
+        assert_eq!(dong[0].stats.count, 2);
+        assert_eq!(dong[0].area_bands.len(), 2);
+        let gu = summarize(&xs, SummaryLevel::Gu);
+        assert_eq!(
+            gu.iter()
+                .map(|x| (x.key.as_str(), x.stats.count))
+                .collect::<Vec<_>>(),
+            vec![("1168000000", 3), ("1171000000", 1)]
+        );
+        assert_eq!(gu[0].rows().len(), 3);
+
+        let xs = vec![
+            listing("5", "116", "84", "9억"),
+            listing("6", "강남구청역", "84", "9억"),
+        ];
+        let gu = summarize(&xs, SummaryLevel::Gu);
+        assert_eq!(
+            gu.iter().map(|x| x.key.as_str()).collect::<Vec<_>>(),
+            ["116", "강남구청역"]
+        );
+    }
+}
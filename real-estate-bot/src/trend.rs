@@ -0,0 +1,322 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::collections::BTreeMap;
+
+use crate::{
+    estate::{ComplexArticle, ListingKey, ListingWithComplex, Price, TradeType},
+    filter::ListingFilter,
+    output::Tabular,
+    snapshot::{price_changed, Snapshot},
+    stats::{show_price, show_unit_price, PriceStats},
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrendQuery {
+    pub complex_id: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub trade_types: Vec<TradeType>,
+}
+
+impl TrendQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn complex_id(mut self, complex_id: &str) -> Self {
+        self.complex_id = Some(complex_id.to_string());
+        self
+    }
+
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn last_days(self, days: i64) -> Self {
+        self.since(Utc::now() - Duration::days(days))
+    }
+
+    pub fn trade_types(mut self, trade_types: Vec<TradeType>) -> Self {
+        self.trade_types = trade_types;
+        self
+    }
+
+    // snapshots in the queried period, oldest first
+    fn snapshots<'a>(&self, snapshots: &'a [Snapshot]) -> Vec<&'a Snapshot> {
+        let mut rs = snapshots
+            .iter()
+            .filter(|x| self.since.is_none_or(|since| x.taken_at >= since))
+            .collect::<Vec<_>>();
+        rs.sort_by_key(|x| x.taken_at);
+        rs
+    }
+
+    fn matches(&self, entry: &ListingWithComplex, filter: &ListingFilter) -> bool {
+        self.complex_id
+            .as_ref()
+            .is_none_or(|x| *x == entry.complex.hscp_no)
+            && filter.matches(&entry.article)
+    }
+
+    fn filter(&self) -> ListingFilter {
+        ListingFilter {
+            trade_types: self.trade_types.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize)]
+pub struct PricePoint {
+    pub at: DateTime<Utc>,
+    pub price: Option<Price>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize)]
+pub struct ArticleHistory {
+    pub key: ListingKey,
+    pub complex_name: String,
+    pub trade_type: Option<String>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub confirmed_at: Option<NaiveDate>,
+    // counted from the earlier of the first sighting and cfmYmd up to the last sighting
+    pub days_on_market: i64,
+    // the first sighting followed by every price change
+    pub prices: Vec<PricePoint>,
+}
+
+impl ArticleHistory {
+    fn new(at: DateTime<Utc>, key: ListingKey, entry: &ListingWithComplex) -> Self {
+        ArticleHistory {
+            key,
+            complex_name: entry.complex.hscp_nm.clone(),
+            trade_type: entry.article.trad_tp_nm.clone(),
+            first_seen: at,
+            last_seen: at,
+            confirmed_at: entry.article.confirmed_date(),
+            days_on_market: 0,
+            prices: vec![PricePoint {
+                at,
+                price: entry.article.price(),
+            }],
+        }
+    }
+
+    pub fn changes(&self) -> usize {
+        self.prices.len() - 1
+    }
+
+    fn listed_on(&self) -> NaiveDate {
+        let first_seen = self.first_seen.date_naive();
+        self.confirmed_at.map_or(first_seen, |x| x.min(first_seen))
+    }
+}
+
+pub fn article_history(snapshots: &[Snapshot], query: &TrendQuery) -> Vec<ArticleHistory> {
+    let filter = query.filter();
+    let mut rs: BTreeMap<ListingKey, (ArticleHistory, ComplexArticle)> = BTreeMap::new();
+    for snapshot in query.snapshots(snapshots) {
+        let at = snapshot.taken_at;
+        for entry in snapshot.entries.iter() {
+            let key = match entry.key() {
+                Some(x) if query.matches(entry, &filter) => x,
+                _ => continue,
+            };
+            match rs.get_mut(&key) {
+                Some((history, last)) => {
+                    history.last_seen = at;
+                    if let Some(x) = entry.article.confirmed_date() {
+                        history.confirmed_at = Some(history.confirmed_at.map_or(x, |y| y.min(x)));
+                    }
+                    if price_changed(last, &entry.article) {
+                        history.prices.push(PricePoint {
+                            at,
+                            price: entry.article.price(),
+                        });
+                    }
+                    *last = entry.article.clone();
+                }
+                None => {
+                    let history = ArticleHistory::new(at, key.clone(), entry);
+                    rs.insert(key, (history, entry.article.clone()));
+                }
+            }
+        }
+    }
+    rs.into_values()
+        .map(|(mut history, _)| {
+            history.days_on_market =
+                (history.last_seen.date_naive() - history.listed_on()).num_days();
+            history
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize)]
+pub struct ComplexTrendPoint {
+    pub hscp_no: String,
+    pub complex_name: String,
+    pub taken_at: DateTime<Utc>,
+    pub stats: PriceStats,
+}
+
+// price statistics of each complex per snapshot, oldest first
+pub fn complex_trend(snapshots: &[Snapshot], query: &TrendQuery) -> Vec<ComplexTrendPoint> {
+    let filter = query.filter();
+    let mut rs = Vec::new();
+    for snapshot in query.snapshots(snapshots) {
+        let mut complexes: BTreeMap<&str, Vec<&ListingWithComplex>> = BTreeMap::new();
+        for entry in snapshot.entries.iter() {
+            if query.matches(entry, &filter) {
+                complexes
+                    .entry(entry.complex.hscp_no.as_str())
+                    .or_default()
+                    .push(entry);
+            }
+        }
+        rs.extend(
+            complexes
+                .into_iter()
+                .map(|(hscp_no, xs)| ComplexTrendPoint {
+                    hscp_no: hscp_no.to_string(),
+                    complex_name: xs[0].complex.hscp_nm.clone(),
+                    taken_at: snapshot.taken_at,
+                    stats: PriceStats::from_listings(xs.iter().copied()),
+                }),
+        );
+    }
+    rs
+}
+
+impl Tabular for ArticleHistory {
+    fn headers() -> Vec<&'static str> {
+        vec![
+            "hscpNo",
+            "atclNo",
+            "complex",
+            "trade",
+            "first seen",
+            "last seen",
+            "confirmed",
+            "days",
+            "changes",
+            "first price",
+            "last price",
+        ]
+    }
+
+    fn row(&self) -> Vec<String> {
+        let price = |x: Option<&PricePoint>| {
+            x.and_then(|x| x.price.as_ref())
+                .map(|x| x.to_string())
+                .unwrap_or_default()
+        };
+        vec![
+            self.key.hscp_no.clone(),
+            self.key.atcl_no.clone(),
+            self.complex_name.clone(),
+            self.trade_type.clone().unwrap_or_default(),
+            self.first_seen.format("%Y-%m-%d %H:%M").to_string(),
+            self.last_seen.format("%Y-%m-%d %H:%M").to_string(),
+            self.confirmed_at.map(|x| x.to_string()).unwrap_or_default(),
+            self.days_on_market.to_string(),
+            self.changes().to_string(),
+            price(self.prices.first()),
+            price(self.prices.last()),
+        ]
+    }
+}
+
+impl Tabular for ComplexTrendPoint {
+    fn headers() -> Vec<&'static str> {
+        vec![
+            "hscpNo", "complex", "taken at", "count", "min", "median", "max", "per ㎡",
+        ]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.hscp_no.clone(),
+            self.complex_name.clone(),
+            self.taken_at.format("%Y-%m-%d %H:%M").to_string(),
+            self.stats.count.to_string(),
+            show_price(&self.stats.min),
+            show_price(&self.stats.median),
+            show_price(&self.stats.max),
+            show_unit_price(&self.stats.median_per_m2),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::estate::Complex;
+    use bigdecimal::BigDecimal;
+    use chrono::TimeZone;
+
+    fn entry(hscp_no: &str, atcl_no: &str, prc_info: &str) -> ListingWithComplex {
+        ListingWithComplex::new(
+            Complex {
+                hscp_no: hscp_no.to_string(),
+                ..Default::default()
+            },
+            ComplexArticle {
+                atcl_no: Some(atcl_no.to_string()),
+                trad_tp_cd: Some(TradeType::Deal),
+                prc_info: Some(prc_info.to_string()),
+                cfm_ymd: Some("21.01.25.".to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn snapshots() -> Vec<Snapshot> {
+        let at = |day| Utc.with_ymd_and_hms(2021, 2, day, 0, 0, 0).unwrap();
+        let snapshot = |day, entries| Snapshot {
+            taken_at: at(day),
+            ..Snapshot::new("4113110800".to_string(), entries)
+        };
+        vec![
+            snapshot(
+                10,
+                vec![entry("8928", "1", "12억"), entry("8928", "2", "10억")],
+            ),
+            snapshot(1, vec![entry("8928", "1", "12억"), entry("1", "3", "5억")]),
+            snapshot(
+                20,
+                vec![entry("8928", "1", "11억"), entry("8928", "2", "10억")],
+            ),
+        ]
+    }
+
+    #[test]
+    fn history_of_articles() {
+        let rs = article_history(&snapshots(), &TrendQuery::new().complex_id("8928"));
+        assert_eq!(rs.len(), 2);
+        assert_eq!(rs[0].key.atcl_no, "1");
+        assert_eq!(rs[0].first_seen.date_naive().to_string(), "2021-02-01");
+        assert_eq!(rs[0].changes(), 1);
+        assert_eq!(
+            rs[0].prices.last().unwrap().price,
+            Some(Price::new(BigDecimal::from(110000)))
+        );
+        // listed on 01-25 according to cfmYmd, last seen on 02-20
+        assert_eq!(rs[0].days_on_market, 26);
+        assert_eq!(rs[1].changes(), 0);
+    }
+
+    #[test]
+    fn trend_of_complex() {
+        let query = TrendQuery::new()
+            .complex_id("8928")
+            .since(Utc.with_ymd_and_hms(2021, 2, 5, 0, 0, 0).unwrap());
+        let rs = complex_trend(&snapshots(), &query);
+        assert_eq!(
+            rs.iter()
+                .map(|x| x.stats.median.clone().unwrap())
+                .collect::<Vec<_>>(),
+            vec![BigDecimal::from(110000), BigDecimal::from(105000)]
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use std::{collections::BTreeSet, fs, path::Path, sync::Arc};
+
+use crate::{
+    crawl::{crawl_region, CrawlFailure, CrawlReport, RegionFailure},
+    estate::{ArticleQuery, Complex, Error, EstateService, ListingWithComplex, Price, TradeType},
+    filter::ListingFilter,
+    output::Tabular,
+};
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct Watchlist {
+    pub rules: Vec<WatchRule>,
+}
+
+// a listing matches when it is in one of `regions` or `complex_ids` (if given) and passes every bound
+#[derive(Debug, Clone, Default, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+pub struct WatchRule {
+    pub name: String,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub regions: Vec<String>,
+    #[serde(default)]
+    pub complex_ids: Vec<String>,
+    #[serde(default)]
+    pub trade_type: Option<String>,
+    #[serde(default)]
+    pub min_area: Option<f64>,
+    #[serde(default)]
+    pub max_area: Option<f64>,
+    // e.g. "12억 5,000"
+    #[serde(default)]
+    pub max_price: Option<String>,
+    #[serde(default)]
+    pub min_floor: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde_derive::Serialize)]
+pub struct WatchMatch {
+    pub rule: String,
+    pub user: Option<String>,
+    pub listing: ListingWithComplex,
+}
+
+// cortarNo is 2 digits of city, 3 of gu and 3 of dong followed by "00"
+pub fn region_contains(region: &str, cortar_no: &str) -> bool {
+    let prefix = if region.ends_with("00000000") {
+        2
+    } else if region.ends_with("00000") {
+        5
+    } else if region.ends_with("00") {
+        8
+    } else {
+        region.len()
+    };
+    region.len() == cortar_no.len() && cortar_no.starts_with(&region[..prefix.min(region.len())])
+}
+
+fn is_dong(region: &str) -> bool {
+    region.len() == 10 && !region.ends_with("00000")
+}
+
+impl Watchlist {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let watchlist: Watchlist = serde_yaml::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| Error::ConfigError(e.to_string()))?;
+        for rule in watchlist.rules.iter() {
+            rule.filter()?;
+        }
+        Ok(watchlist)
+    }
+
+    pub fn evaluate(&self, listings: &[ListingWithComplex]) -> Result<Vec<WatchMatch>, Error> {
+        let rules = self
+            .rules
+            .iter()
+            .map(|x| x.filter().map(|filter| (x, filter)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rules
+            .iter()
+            .flat_map(|(rule, filter)| {
+                listings
+                    .iter()
+                    .filter(move |x| rule.in_scope(x) && filter.matches(&x.article))
+                    .map(move |x| WatchMatch {
+                        rule: rule.name.clone(),
+                        user: rule.user.clone(),
+                        listing: x.clone(),
+                    })
+            })
+            .collect())
+    }
+}
+
+impl WatchRule {
+    pub fn filter(&self) -> Result<ListingFilter, Error> {
+        let err = |e: String| Error::ConfigError(format!("{}: {}", self.name, e));
+        let trade_types = match &self.trade_type {
+            Some(x) => vec![x.parse::<TradeType>().map_err(err)?],
+            None => Vec::new(),
+        };
+        let max_price = match &self.max_price {
+            Some(x) => Some(x.parse::<Price>().map_err(|e| err(e.to_string()))?.amount),
+            None => None,
+        };
+        Ok(ListingFilter {
+            trade_types,
+            min_area: self.min_area,
+            max_area: self.max_area,
+            max_price,
+            min_floor: self.min_floor,
+            ..Default::default()
+        })
+    }
+
+    fn in_scope(&self, listing: &ListingWithComplex) -> bool {
+        (self.regions.is_empty() && self.complex_ids.is_empty())
+            || self
+                .regions
+                .iter()
+                .any(|x| region_contains(x, &listing.complex.cortar_no))
+            || self.complex_ids.contains(&listing.complex.hscp_no)
+    }
+}
+
+// crawls every region and complex the rules refer to, each only once.
+// a region or complex that fails is recorded in the report and the others are still crawled
+pub async fn crawl_watchlist<T: EstateService>(
+    service: Arc<T>,
+    watchlist: &Watchlist,
+    concurrency: usize,
+) -> Result<CrawlReport, Error> {
+    let regions = watchlist
+        .rules
+        .iter()
+        .flat_map(|x| x.regions.iter().cloned())
+        .collect::<BTreeSet<_>>();
+    let mut report = CrawlReport::default();
+    for region in regions.iter() {
+        match crawl_region(
+            service.clone(),
+            region.clone(),
+            !is_dong(region),
+            ArticleQuery::all(),
+            concurrency,
+        )
+        .await
+        {
+            Ok(x) => report.merge(x),
+            Err(error) => report.region_failures.push(RegionFailure {
+                region_no: region.clone(),
+                error,
+            }),
+        }
+    }
+    let crawled = report
+        .entries
+        .iter()
+        .map(|x| x.complex.hscp_no.clone())
+        .collect::<BTreeSet<_>>();
+    let complex_ids = watchlist
+        .rules
+        .iter()
+        .flat_map(|x| x.complex_ids.iter().cloned())
+        .filter(|x| !crawled.contains(x))
+        .collect::<BTreeSet<_>>();
+    for complex_id in complex_ids {
+        let complex = Complex {
+            hscp_no: complex_id.clone(),
+            ..Default::default()
+        };
+        match service
+            .clone()
+            .complex_article_list(complex_id.clone(), ArticleQuery::all())
+            .await
+        {
+            Ok(xs) => report.entries.extend(
+                xs.into_iter()
+                    .map(|x| ListingWithComplex::new(complex.clone(), x)),
+            ),
+            Err(error) => report.failures.push(CrawlFailure { complex_id, error }),
+        }
+    }
+    Ok(report)
+}
+
+impl Tabular for WatchMatch {
+    fn headers() -> Vec<&'static str> {
+        let mut rs = vec!["rule", "user"];
+        rs.extend(ListingWithComplex::headers());
+        rs
+    }
+
+    fn row(&self) -> Vec<String> {
+        let mut rs = vec![self.rule.clone(), self.user.clone().unwrap_or_default()];
+        rs.extend(self.listing.row());
+        rs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        estate::{ComplexArticle, CortarType},
+        fake::{EstateServiceFake, FakeCall},
+    };
+
+    fn listing(hscp_no: &str, cortar_no: &str, spc2: &str, prc_info: &str) -> ListingWithComplex {
+        ListingWithComplex::new(
+            Complex {
+                hscp_no: hscp_no.to_string(),
+                cortar_no: cortar_no.to_string(),
+                ..Default::default()
+            },
+            ComplexArticle {
+                atcl_no: Some(format!("{}-{}", hscp_no, prc_info)),
+                trad_tp_cd: Some(TradeType::Deal),
+                spc2: Some(spc2.to_string()),
+                prc_info: Some(prc_info.to_string()),
+                flr_info: Some("10/20".to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn watchlist() -> Watchlist {
+        serde_yaml::from_str(
+            "
+rules:
+  - name: gangnam-34py
+    user: ben
+    regions: ['1168000000']
+    trade_type: deal
+    min_area: 80
+    max_area: 90
+    max_price: 25억
+    min_floor: 5
+  - name: wirye
+    complex_ids: ['8928']
+",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn region_levels() {
+        assert!(region_contains("1100000000", "1168010300"));
+        assert!(region_contains("1168000000", "1168010300"));
+        assert!(!region_contains("1168000000", "1171010100"));
+        assert!(region_contains("1168010300", "1168010300"));
+        assert!(!region_contains("1168010300", "1168010600"));
+    }
+
+    #[test]
+    fn evaluate_rules() {
+        let listings = vec![
+            listing("1", "1168010300", "84.9", "24억"),
+            listing("1", "1168010300", "84.9", "26억"),
+            listing("2", "1168010600", "59.9", "18억"),
+            listing("3", "1171010100", "84.9", "15억"),
+            listing("8928", "4113110800", "84.9", "12억"),
+        ];
+        let rs = watchlist().evaluate(&listings).unwrap();
+        assert_eq!(
+            rs.iter()
+                .map(|x| (x.rule.as_str(), x.listing.article.prc_info.clone().unwrap()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("gangnam-34py", "24억".to_string()),
+                ("wirye", "12억".to_string())
+            ]
+        );
+        assert_eq!(rs[0].user.as_deref(), Some("ben"));
+    }
+
+    #[tokio::test]
+    async fn crawl_each_target_once() {
+        let service = EstateServiceFake::new()
+            .with_regions(
+                "1168000000",
+                vec![crate::estate::Region {
+                    cortar_no: "1168010300".to_string(),
+                    cortar_type: CortarType::Section,
+                    ..Default::default()
+                }],
+            )
+            .with_complexes(
+                "1168010300",
+                vec![Complex {
+                    hscp_no: "1".to_string(),
+                    ..Default::default()
+                }],
+            )
+            .with_articles("1", vec![listing("1", "", "84.9", "24억").article])
+            .with_articles("8928", vec![listing("8928", "", "84.9", "12억").article]);
+        let report = crawl_watchlist(Arc::new(service.clone()), &watchlist(), 2)
+            .await
+            .unwrap();
+        assert_eq!(report.entries.len(), 2);
+        assert_eq!(service.calls().len(), 4);
+
+        // a failing region doesn't stop the rest of the watchlist
+        let service = service.fail_always(FakeCall::RegionList("1168000000".to_string()));
+        let report = crawl_watchlist(Arc::new(service), &watchlist(), 2)
+            .await
+            .unwrap();
+        assert_eq!(report.entries.len(), 1);
+        assert_eq!(report.failed_region_nos(), ["1168000000"]);
+    }
+}
@@ -0,0 +1,114 @@
+use axum::{
+    extract::{Query, State},
+    http::{StatusCode, Uri},
+    Router,
+};
+use real_estate_bot::{
+    estate::{EstateConfig, EstateServiceLive},
+    retry::RetryPolicy,
+};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+// canned responses keyed by "region/{cortarNo}", "complex/{cortarNo}" or "article/{hscpNo}/{page}".
+// each key holds a queue, the last response is repeated once the queue is drained.
+#[derive(Default)]
+struct FixtureState {
+    responses: Mutex<HashMap<String, Vec<(u16, String)>>>,
+    hits: Mutex<HashMap<String, usize>>,
+}
+
+pub struct FixtureServer {
+    pub base_url: String,
+    state: Arc<FixtureState>,
+}
+
+pub fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    std::fs::read_to_string(path).unwrap()
+}
+
+fn request_key(uri: &Uri, q: &HashMap<String, String>) -> String {
+    let param = |name: &str| q.get(name).cloned().unwrap_or_default();
+    match uri.path() {
+        "/map/getRegionList" => format!("region/{}", param("cortarNo")),
+        "/complex/ajax/complexListByCortarNo" => format!("complex/{}", param("cortarNo")),
+        "/complex/getComplexArticleList" => {
+            format!("article/{}/{}", param("hscpNo"), param("page"))
+        }
+        x => x.to_string(),
+    }
+}
+
+async fn handle(
+    State(state): State<Arc<FixtureState>>,
+    uri: Uri,
+    Query(q): Query<HashMap<String, String>>,
+) -> (StatusCode, String) {
+    let key = request_key(&uri, &q);
+    *state.hits.lock().unwrap().entry(key.clone()).or_default() += 1;
+    let mut responses = state.responses.lock().unwrap();
+    let (status, body) = match responses.get_mut(&key) {
+        Some(xs) if xs.len() > 1 => xs.remove(0),
+        Some(xs) if !xs.is_empty() => xs[0].clone(),
+        _ => (404, String::new()),
+    };
+    (StatusCode::from_u16(status).unwrap(), body)
+}
+
+impl FixtureServer {
+    pub async fn start() -> Self {
+        let state = Arc::new(FixtureState::default());
+        let app = Router::new().fallback(handle).with_state(state.clone());
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(app.into_make_service());
+        let base_url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        FixtureServer { base_url, state }
+    }
+
+    pub fn respond(&self, key: &str, status: u16, body: impl Into<String>) -> &Self {
+        self.state
+            .responses
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .push((status, body.into()));
+        self
+    }
+
+    pub fn hits(&self, key: &str) -> usize {
+        self.state
+            .hits
+            .lock()
+            .unwrap()
+            .get(key)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn config(&self) -> EstateConfig {
+        EstateConfig {
+            base_url: self.base_url.clone(),
+            requests_per_second: 0.0,
+            timeout: Duration::from_secs(2),
+            retry: RetryPolicy {
+                max_retries: 2,
+                base_delay: Duration::from_millis(10),
+                max_delay: Duration::from_millis(20),
+            },
+        }
+    }
+
+    pub fn service(&self) -> Arc<EstateServiceLive> {
+        Arc::new(EstateServiceLive::new(self.config()))
+    }
+}
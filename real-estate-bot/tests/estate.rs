@@ -0,0 +1,123 @@
+mod common;
+
+use common::{fixture, FixtureServer};
+use real_estate_bot::estate::{ArticleQuery, Error, EstateService};
+
+#[tokio::test]
+async fn region_and_complex_list() {
+    let server = FixtureServer::start().await;
+    server
+        .respond("region/1168000000", 200, fixture("region_list.json"))
+        .respond("complex/1168010300", 200, fixture("complex_list.json"));
+    let service = server.service();
+
+    let regions = service
+        .clone()
+        .region_list("1168000000".to_string())
+        .await
+        .unwrap();
+    assert_eq!(
+        regions
+            .iter()
+            .map(|x| x.cortar_nm.as_str())
+            .collect::<Vec<_>>(),
+        vec!["개포동", "대치동"]
+    );
+
+    let complexes = service
+        .complex_list("1168010300".to_string())
+        .await
+        .unwrap();
+    assert_eq!(complexes.len(), 1);
+    assert_eq!(complexes[0].hscp_no, "8928");
+}
+
+#[tokio::test]
+async fn article_list_follows_more_data_yn() {
+    let server = FixtureServer::start().await;
+    server
+        .respond("article/8928/1", 200, fixture("article_list_page1.json"))
+        .respond("article/8928/2", 200, fixture("article_list_page2.json"));
+
+    let xs = server
+        .service()
+        .complex_article_list("8928".to_string(), ArticleQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        xs.iter()
+            .map(|x| x.atcl_no.clone().unwrap())
+            .collect::<Vec<_>>(),
+        vec!["2100000001", "2100000002", "2100000003"]
+    );
+    assert_eq!(xs[1].price(), "24억 5,000".parse().ok());
+    assert_eq!(server.hits("article/8928/1"), 1);
+    assert_eq!(server.hits("article/8928/2"), 1);
+    assert_eq!(server.hits("article/8928/3"), 0);
+}
+
+#[tokio::test]
+async fn malformed_json() {
+    let server = FixtureServer::start().await;
+    server.respond("region/1168000000", 200, "{\"result\": {\"list\": [");
+
+    let rs = server.service().region_list("1168000000".to_string()).await;
+    assert!(matches!(rs, Err(Error::SerdeError(_))));
+}
+
+#[tokio::test]
+async fn http_error_is_not_retried() {
+    let server = FixtureServer::start().await;
+    server.respond("complex/1168010300", 404, "");
+
+    let rs = server
+        .service()
+        .complex_list("1168010300".to_string())
+        .await;
+    assert!(matches!(rs, Err(Error::ReqwestError(_))));
+    assert_eq!(server.hits("complex/1168010300"), 1);
+}
+
+#[tokio::test]
+async fn server_error_is_retried() {
+    let server = FixtureServer::start().await;
+    server
+        .respond("article/8928/1", 200, fixture("article_list_page1.json"))
+        .respond("article/8928/2", 503, "")
+        .respond("article/8928/2", 200, fixture("article_list_page2.json"));
+
+    let xs = server
+        .service()
+        .complex_article_list("8928".to_string(), ArticleQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(xs.len(), 3);
+    assert_eq!(server.hits("article/8928/2"), 2);
+}
+
+#[tokio::test]
+async fn retries_run_out() {
+    let server = FixtureServer::start().await;
+    server
+        .respond("article/8928/1", 200, fixture("article_list_page1.json"))
+        .respond("article/8928/2", 429, "");
+
+    let rs = server
+        .service()
+        .complex_article_list("8928".to_string(), ArticleQuery::default())
+        .await;
+    match rs {
+        Err(Error::RetryExhausted {
+            url,
+            page,
+            attempts,
+            ..
+        }) => {
+            assert!(url.contains("hscpNo=8928"));
+            assert_eq!(page, Some(2));
+            assert_eq!(attempts, 3);
+        }
+        x => panic!("unexpected result: {:?}", x),
+    }
+    assert_eq!(server.hits("article/8928/2"), 3);
+}
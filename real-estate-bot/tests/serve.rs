@@ -0,0 +1,128 @@
+use chrono::{Duration, TimeZone, Utc};
+use real_estate_bot::{
+    estate::{Complex, ComplexArticle, ListingWithComplex},
+    serve::router,
+    snapshot::{Snapshot, SnapshotStore},
+};
+use serde_json::Value;
+use std::net::SocketAddr;
+
+fn listing(hscp_no: &str, atcl_no: &str, prc_info: &str) -> ListingWithComplex {
+    ListingWithComplex::new(
+        Complex {
+            hscp_no: hscp_no.to_string(),
+            hscp_nm: format!("단지 {}", hscp_no),
+            cortar_no: "1168010300".to_string(),
+            ..Default::default()
+        },
+        ComplexArticle {
+            atcl_no: Some(atcl_no.to_string()),
+            trad_tp_nm: Some("매매".to_string()),
+            prc_info: Some(prc_info.to_string()),
+            ..Default::default()
+        },
+    )
+}
+
+async fn start(store: SnapshotStore) -> String {
+    let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+        .serve(router(store).into_make_service());
+    let base_url = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+    base_url
+}
+
+async fn get(base_url: &str, path: &str) -> (u16, Value) {
+    let res = reqwest::get(format!("{}{}", base_url, path)).await.unwrap();
+    (res.status().as_u16(), res.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn serves_latest_snapshots() {
+    let path = std::env::temp_dir().join(format!(
+        "real-estate-bot-serve-{}.jsonl",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let store = SnapshotStore::new(&path);
+    let taken_at = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+    let prev = Snapshot {
+        taken_at,
+        ..Snapshot::new(
+            "1168010300".to_string(),
+            vec![
+                listing("8928", "1", "12억 5,000"),
+                listing("8928", "2", "9억"),
+            ],
+        )
+    };
+    let cur = Snapshot {
+        taken_at: taken_at + Duration::days(1),
+        ..Snapshot::new(
+            "1168010300".to_string(),
+            vec![
+                listing("8928", "1", "12억"),
+                listing("8928", "3", "8억"),
+                listing("1", "4", "15억"),
+            ],
+        )
+    };
+    store.append(&prev).unwrap();
+    store.append(&cur).unwrap();
+    let base_url = start(store).await;
+
+    let (_, rs) = get(&base_url, "/regions").await;
+    assert_eq!(rs["total"], 1);
+    assert_eq!(rs["items"][0]["complexes"], 2);
+    assert_eq!(rs["items"][0]["listings"], 3);
+
+    let (_, rs) = get(&base_url, "/complexes?region=1168000000&size=1&page=2").await;
+    assert_eq!(rs["total"], 2);
+    assert_eq!(rs["items"].as_array().unwrap().len(), 1);
+    assert_eq!(rs["items"][0]["hscpNo"], "8928");
+
+    let (_, rs) = get(&base_url, "/articles?complex=8928&max_price=10억").await;
+    assert_eq!(rs["total"], 1);
+    assert_eq!(rs["items"][0]["article"]["atclNo"], "3");
+
+    // past the end is an empty page rather than an overflow
+    let (status, rs) = get(
+        &base_url,
+        &format!("/articles?page={}&size=500", usize::MAX),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(rs["total"], 3);
+    assert_eq!(rs["items"].as_array().unwrap().len(), 0);
+    let (_, rs) = get(&base_url, "/articles?page=0&size=1").await;
+    assert_eq!(rs["page"], 1);
+    assert_eq!(rs["items"].as_array().unwrap().len(), 1);
+
+    let (status, rs) = get(&base_url, "/articles?min_price=abc").await;
+    assert_eq!(status, 400);
+    assert!(rs["error"].is_string());
+
+    let (_, rs) = get(&base_url, "/changes?since=2024-03-02").await;
+    let kinds = rs["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["kind"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, ["added", "added", "removed", "price_changed"]);
+
+    let (_, rs) = get(&base_url, "/changes?since=2024-03-03").await;
+    assert_eq!(rs["total"], 0);
+
+    // a crawl appending to the file shows up on the next request
+    SnapshotStore::new(&path)
+        .append(&Snapshot::new(
+            "1168010600".to_string(),
+            vec![listing("2", "5", "20억")],
+        ))
+        .unwrap();
+    let (_, rs) = get(&base_url, "/regions").await;
+    assert_eq!(rs["total"], 2);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(path.with_extension("latest.json")).unwrap();
+}